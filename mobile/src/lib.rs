@@ -4,17 +4,25 @@ use camp_game::GamePlugin; // ToDo: Replace camp_game with your new crate name.
 
 #[bevy_main]
 fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    resizable: false,
-                    mode: WindowMode::BorderlessFullscreen,
-                    ..default()
-                }),
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                resizable: false,
+                mode: WindowMode::BorderlessFullscreen,
                 ..default()
             }),
-            GamePlugin,
-        ))
-        .run()
+            ..default()
+        }),
+        GamePlugin,
+    ));
+    // Android apps can only write inside their own sandbox
+    #[cfg(target_os = "android")]
+    if let Some(path) = bevy::winit::ANDROID_APP
+        .get()
+        .and_then(|android_app| android_app.internal_data_path())
+    {
+        app.insert_resource(camp_game::SaveDirectory(path));
+    }
+    app.run()
 }
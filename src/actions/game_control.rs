@@ -1,35 +1,180 @@
-use bevy::prelude::{ButtonInput, KeyCode, Res};
+use bevy::prelude::{ButtonInput, GamepadButton, GamepadButtonType, KeyCode, Res};
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GameControl {
     Up,
     Down,
     Left,
     Right,
+    Interact,
+    Cancel,
+    QuickSave,
 }
 
 impl GameControl {
-    pub fn pressed(&self, keyboard_input: &Res<ButtonInput<KeyCode>>) -> bool {
+    /// Every control in the order the settings menu lists them
+    pub const ALL: [GameControl; 7] = [
+        GameControl::Up,
+        GameControl::Down,
+        GameControl::Left,
+        GameControl::Right,
+        GameControl::Interact,
+        GameControl::Cancel,
+        GameControl::QuickSave,
+    ];
+
+    pub fn label(&self) -> &'static str {
         match self {
-            GameControl::Up => {
-                keyboard_input.pressed(KeyCode::KeyW) || keyboard_input.pressed(KeyCode::ArrowUp)
-            }
-            GameControl::Down => {
-                keyboard_input.pressed(KeyCode::KeyS) || keyboard_input.pressed(KeyCode::ArrowDown)
-            }
-            GameControl::Left => {
-                keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft)
-            }
-            GameControl::Right => {
-                keyboard_input.pressed(KeyCode::KeyD) || keyboard_input.pressed(KeyCode::ArrowRight)
+            GameControl::Up => "Up",
+            GameControl::Down => "Down",
+            GameControl::Left => "Left",
+            GameControl::Right => "Right",
+            GameControl::Interact => "Interact",
+            GameControl::Cancel => "Cancel",
+            GameControl::QuickSave => "Quick save",
+        }
+    }
+
+    fn gamepad_buttons(&self) -> &'static [GamepadButtonType] {
+        match self {
+            GameControl::Up => &[GamepadButtonType::DPadUp],
+            GameControl::Down => &[GamepadButtonType::DPadDown],
+            GameControl::Left => &[GamepadButtonType::DPadLeft],
+            GameControl::Right => &[GamepadButtonType::DPadRight],
+            GameControl::Interact => &[GamepadButtonType::South],
+            GameControl::Cancel => &[GamepadButtonType::East],
+            GameControl::QuickSave => &[GamepadButtonType::Select],
+        }
+    }
+
+    pub fn pressed(
+        &self,
+        bindings: &KeyBindings,
+        keyboard_input: &Res<ButtonInput<KeyCode>>,
+    ) -> bool {
+        keyboard_input.any_pressed(bindings.keys(*self).iter().copied())
+    }
+
+    pub fn just_pressed(
+        &self,
+        bindings: &KeyBindings,
+        keyboard_input: &Res<ButtonInput<KeyCode>>,
+        gamepad_input: &Res<ButtonInput<GamepadButton>>,
+    ) -> bool {
+        keyboard_input.any_just_pressed(bindings.keys(*self).iter().copied())
+            || gamepad_input
+                .get_just_pressed()
+                .any(|button| self.gamepad_buttons().contains(&button.button_type))
+    }
+}
+
+/// Keyboard keys for every control, the first key of each is the one that can be rebound
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub interact: Vec<KeyCode>,
+    pub cancel: Vec<KeyCode>,
+    pub quick_save: Vec<KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            up: vec![KeyCode::KeyW, KeyCode::ArrowUp],
+            down: vec![KeyCode::KeyS, KeyCode::ArrowDown],
+            left: vec![KeyCode::KeyA, KeyCode::ArrowLeft],
+            right: vec![KeyCode::KeyD, KeyCode::ArrowRight],
+            interact: vec![KeyCode::KeyE, KeyCode::Space, KeyCode::Enter],
+            cancel: vec![KeyCode::Escape],
+            quick_save: vec![KeyCode::F5],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, control: GameControl) -> &[KeyCode] {
+        match control {
+            GameControl::Up => &self.up,
+            GameControl::Down => &self.down,
+            GameControl::Left => &self.left,
+            GameControl::Right => &self.right,
+            GameControl::Interact => &self.interact,
+            GameControl::Cancel => &self.cancel,
+            GameControl::QuickSave => &self.quick_save,
+        }
+    }
+
+    fn keys_mut(&mut self, control: GameControl) -> &mut Vec<KeyCode> {
+        match control {
+            GameControl::Up => &mut self.up,
+            GameControl::Down => &mut self.down,
+            GameControl::Left => &mut self.left,
+            GameControl::Right => &mut self.right,
+            GameControl::Interact => &mut self.interact,
+            GameControl::Cancel => &mut self.cancel,
+            GameControl::QuickSave => &mut self.quick_save,
+        }
+    }
+
+    /// Replaces the primary key of `control` and keeps its alternatives
+    ///
+    /// The key is taken away from every other control. A control left without keys gets the
+    /// replaced primary key instead, so the two controls swap.
+    pub fn rebind(&mut self, control: GameControl, key: KeyCode) {
+        let replaced = self.keys(control).first().copied();
+        for other in GameControl::ALL
+            .into_iter()
+            .filter(|other| *other != control)
+        {
+            let keys = self.keys_mut(other);
+            keys.retain(|bound| *bound != key);
+            if let (true, Some(replaced)) = (keys.is_empty(), replaced) {
+                keys.push(replaced);
             }
         }
+        let keys = self.keys_mut(control);
+        keys.retain(|bound| *bound != key);
+        match keys.first_mut() {
+            Some(primary) => *primary = key,
+            None => keys.push(key),
+        }
     }
 }
 
-pub fn get_movement(control: GameControl, input: &Res<ButtonInput<KeyCode>>) -> f32 {
-    if control.pressed(input) {
+pub fn get_movement(
+    control: GameControl,
+    bindings: &KeyBindings,
+    input: &Res<ButtonInput<KeyCode>>,
+) -> f32 {
+    if control.pressed(bindings, input) {
         1.0
     } else {
         0.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_takes_the_key_from_other_controls() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(GameControl::Up, KeyCode::Space);
+        assert_eq!(bindings.up, vec![KeyCode::Space, KeyCode::ArrowUp]);
+        assert_eq!(bindings.interact, vec![KeyCode::KeyE, KeyCode::Enter]);
+    }
+
+    #[test]
+    fn rebinding_the_only_key_of_a_control_swaps() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(GameControl::QuickSave, KeyCode::Escape);
+        assert_eq!(bindings.quick_save, vec![KeyCode::Escape]);
+        assert_eq!(bindings.cancel, vec![KeyCode::F5]);
+    }
+}
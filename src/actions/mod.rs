@@ -1,12 +1,17 @@
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 
-use crate::actions::game_control::{get_movement, GameControl};
-use crate::player::Player;
+use crate::actions::game_control::get_movement;
+use crate::dialogue::{ActiveDialogue, StartDialogue};
+use crate::navigation::NavGrid;
+use crate::player::{GameCamera, Player};
+use crate::settings::Settings;
 use crate::GameState;
 
 mod game_control;
 
+pub use game_control::{GameControl, KeyBindings};
+
 pub const FOLLOW_EPSILON: f32 = 5.;
 
 pub struct ActionsPlugin;
@@ -15,40 +20,151 @@ pub struct ActionsPlugin;
 // Actions can then be used as a resource in other systems to act on the player input.
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Actions>().add_systems(
-            Update,
-            set_movement_actions.run_if(in_state(GameState::Playing)),
-        );
+        app.init_resource::<Actions>()
+            .init_resource::<MoveTarget>()
+            .add_systems(
+                Update,
+                (
+                    set_button_actions,
+                    (set_move_target, set_movement_actions)
+                        .run_if(not(resource_exists::<ActiveDialogue>)),
+                    update_destination_marker,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), clear_move_target);
     }
 }
 
 #[derive(Default, Resource)]
 pub struct Actions {
     pub player_movement: Option<Vec2>,
+    /// The following are only true in the frame the input was pressed
+    pub interact: bool,
+    pub cancel: bool,
+    pub select_previous: bool,
+    pub select_next: bool,
+    pub quick_save: bool,
 }
 
-pub fn set_movement_actions(
+pub fn set_button_actions(
     mut actions: ResMut<Actions>,
+    settings: Res<Settings>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+) {
+    let bindings = &settings.key_bindings;
+    actions.interact =
+        GameControl::Interact.just_pressed(bindings, &keyboard_input, &gamepad_input);
+    actions.cancel = GameControl::Cancel.just_pressed(bindings, &keyboard_input, &gamepad_input);
+    actions.select_previous =
+        GameControl::Up.just_pressed(bindings, &keyboard_input, &gamepad_input);
+    actions.select_next = GameControl::Down.just_pressed(bindings, &keyboard_input, &gamepad_input);
+    actions.quick_save =
+        GameControl::QuickSave.just_pressed(bindings, &keyboard_input, &gamepad_input);
+}
+
+/// Where the player was told to go by clicking or tapping on the world
+#[derive(Default, Resource)]
+pub struct MoveTarget {
+    pub destination: Option<Vec2>,
+    /// Remaining waypoints towards `destination`, computed on the navigation grid
+    pub path: Vec<Vec2>,
+}
+
+impl MoveTarget {
+    pub fn cancel(&mut self) {
+        self.destination = None;
+        self.path.clear();
+    }
+}
+
+#[derive(Component)]
+struct DestinationMarker;
+
+#[allow(clippy::too_many_arguments)]
+pub fn set_move_target(
+    mut move_target: ResMut<MoveTarget>,
+    mut start_dialogue: EventReader<StartDialogue>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
     touch_input: Res<Touches>,
+    windows: Query<&Window>,
+    nav_grid: Res<NavGrid>,
+    player: Query<&Transform, With<Player>>,
+    camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+) {
+    let screen_position = if mouse_input.just_pressed(MouseButton::Left) {
+        windows.get_single().ok().and_then(Window::cursor_position)
+    } else {
+        touch_input
+            .iter_just_pressed()
+            .next()
+            .map(|touch| touch.position())
+    };
+    // A tap on a neighbor starts talking to them instead of walking there
+    let talking = start_dialogue.read().count() > 0;
+    let Some(screen_position) = screen_position.filter(|_| !talking) else {
+        return;
+    };
+    let (Ok((camera, camera_transform)), Ok(player_transform)) =
+        (camera.get_single(), player.get_single())
+    else {
+        return;
+    };
+    let Some(destination) = camera.viewport_to_world_2d(camera_transform, screen_position) else {
+        return;
+    };
+    match nav_grid.find_path(player_transform.translation.xy(), destination) {
+        Some(path) => {
+            move_target.destination = path.last().copied();
+            move_target.path = path;
+        }
+        None => move_target.cancel(),
+    }
+}
+
+pub fn set_movement_actions(
+    mut actions: ResMut<Actions>,
+    mut move_target: ResMut<MoveTarget>,
+    settings: Res<Settings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
     player: Query<&Transform, With<Player>>,
-    camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
 ) {
+    let bindings = &settings.key_bindings;
     let mut player_movement = Vec2::new(
-        get_movement(GameControl::Right, &keyboard_input)
-            - get_movement(GameControl::Left, &keyboard_input),
-        get_movement(GameControl::Up, &keyboard_input)
-            - get_movement(GameControl::Down, &keyboard_input),
+        get_movement(GameControl::Right, bindings, &keyboard_input)
+            - get_movement(GameControl::Left, bindings, &keyboard_input),
+        get_movement(GameControl::Up, bindings, &keyboard_input)
+            - get_movement(GameControl::Down, bindings, &keyboard_input),
     );
 
-    if let Some(touch_position) = touch_input.first_pressed_position() {
-        let (camera, camera_transform) = camera.single();
-        if let Some(touch_position) = camera.viewport_to_world_2d(camera_transform, touch_position)
-        {
-            let diff = touch_position - player.single().translation.xy();
+    // Any key, holding a direction or cancelling takes back control from click-to-move
+    let steering = [
+        GameControl::Up,
+        GameControl::Down,
+        GameControl::Left,
+        GameControl::Right,
+    ]
+    .iter()
+    .any(|control| control.pressed(bindings, &keyboard_input));
+    let any_key = keyboard_input.get_just_pressed().next().is_some();
+    if move_target.destination.is_some() && (steering || any_key || actions.cancel) {
+        move_target.cancel();
+    }
+
+    if let Ok(player_transform) = player.get_single() {
+        let position = player_transform.translation.xy();
+        while let Some(&waypoint) = move_target.path.first() {
+            let diff = waypoint - position;
             if diff.length() > FOLLOW_EPSILON {
                 player_movement = diff.normalize();
+                break;
             }
+            move_target.path.remove(0);
+        }
+        if move_target.path.is_empty() && move_target.destination.is_some() {
+            move_target.destination = None;
         }
     }
 
@@ -58,3 +174,47 @@ pub fn set_movement_actions(
         actions.player_movement = None;
     }
 }
+
+fn update_destination_marker(
+    mut commands: Commands,
+    move_target: Res<MoveTarget>,
+    mut marker: Query<(Entity, &mut Transform), With<DestinationMarker>>,
+) {
+    if !move_target.is_changed() {
+        return;
+    }
+    match (move_target.destination, marker.get_single_mut()) {
+        (Some(destination), Ok((_, mut transform))) => {
+            transform.translation = destination.extend(transform.translation.z);
+        }
+        (Some(destination), Err(_)) => {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.7),
+                        custom_size: Some(Vec2::splat(10.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(destination.extend(1.5))
+                        .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+                    ..default()
+                },
+                DestinationMarker,
+            ));
+        }
+        (None, Ok((entity, _))) => commands.entity(entity).despawn_recursive(),
+        (None, Err(_)) => {}
+    }
+}
+
+/// The player stops walking to the destination when the game is left, e.g. for the menu
+fn clear_move_target(
+    mut commands: Commands,
+    mut move_target: ResMut<MoveTarget>,
+    marker: Query<Entity, With<DestinationMarker>>,
+) {
+    move_target.cancel();
+    for entity in &marker {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::sprite::TextureAtlasBuilderError;
+use bevy::utils::HashMap;
+use std::fmt;
+
+/// The largest atlas that is packed, most devices support textures at least this big
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+/// Packs groups of loaded images, like `TextureAssets::driveway`, into a single atlas at runtime
+/// The atlas starts out just big enough for the images and grows until they fit
+#[derive(SystemParam)]
+pub struct AtlasPacker<'w> {
+    images: ResMut<'w, Assets<Image>>,
+    layouts: ResMut<'w, Assets<TextureAtlasLayout>>,
+}
+
+/// The images of a group packed into one, with the layout to find each of them in it
+pub struct PackedAtlas {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    by_source: HashMap<AssetId<Image>, usize>,
+}
+
+impl PackedAtlas {
+    /// The layout's index of a source image, `NotPacked` if it wasn't packed into this atlas
+    pub fn index(&self, source: &Handle<Image>) -> Result<usize, AtlasError> {
+        self.by_source
+            .get(&source.id())
+            .copied()
+            .ok_or_else(|| AtlasError::NotPacked(describe(source)))
+    }
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// There were no images to pack
+    Empty,
+    /// A source image isn't loaded (yet), with its path if it has one
+    NotLoaded(String),
+    /// The images don't fit into an atlas of the largest size
+    TooLarge { max_size: u32 },
+    /// An image has a format that can't be converted to the atlas' format
+    WrongFormat,
+    /// An image that was looked up in an atlas isn't part of it
+    NotPacked(String),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::Empty => write!(f, "there are no images to pack"),
+            AtlasError::NotLoaded(path) => write!(f, "image {path} is not loaded"),
+            AtlasError::TooLarge { max_size } => {
+                write!(f, "images don't fit into a {max_size}x{max_size} atlas")
+            }
+            AtlasError::WrongFormat => write!(f, "an image has a format the atlas can't take"),
+            AtlasError::NotPacked(path) => write!(f, "image {path} is not in the atlas"),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl AtlasPacker<'_> {
+    pub fn pack(&mut self, sources: &[Handle<Image>]) -> Result<PackedAtlas, AtlasError> {
+        if sources.is_empty() {
+            return Err(AtlasError::Empty);
+        }
+        let mut textures = Vec::with_capacity(sources.len());
+        for handle in sources {
+            let image = self
+                .images
+                .get(handle)
+                .ok_or_else(|| AtlasError::NotLoaded(describe(handle)))?;
+            textures.push((handle.id(), image));
+        }
+
+        let mut builder = TextureAtlasBuilder::default()
+            .initial_size(initial_size(textures.iter().map(|(_, image)| image.size())))
+            .max_size(Vec2::splat(MAX_ATLAS_SIZE as f32));
+        for (id, image) in textures {
+            builder.add_texture(Some(id), image);
+        }
+        let (layout, image) = builder.finish().map_err(|error| match error {
+            TextureAtlasBuilderError::NotEnoughSpace => AtlasError::TooLarge {
+                max_size: MAX_ATLAS_SIZE,
+            },
+            TextureAtlasBuilderError::WrongFormat => AtlasError::WrongFormat,
+        })?;
+
+        let by_source = sources
+            .iter()
+            .filter_map(|handle| Some((handle.id(), layout.get_texture_index(handle)?)))
+            .collect();
+        Ok(PackedAtlas {
+            image: self.images.add(image),
+            layout: self.layouts.add(layout),
+            by_source,
+        })
+    }
+
+    /// Packs `sources` again into the `image` and `layout` of an atlas packed from them,
+    /// so everything drawn with it shows the images as they are now
+    /// The atlas' indices stay the same as long as the images keep their sizes
+    #[cfg(feature = "dev")]
+    pub fn repack(
+        &mut self,
+        sources: &[Handle<Image>],
+        image: &Handle<Image>,
+        layout: &Handle<TextureAtlasLayout>,
+    ) -> Result<(), AtlasError> {
+        let packed = self.pack(sources)?;
+        if let Some(packed_image) = self.images.remove(&packed.image) {
+            self.images.insert(image, packed_image);
+        }
+        if let Some(packed_layout) = self.layouts.remove(&packed.layout) {
+            self.layouts.insert(layout, packed_layout);
+        }
+        Ok(())
+    }
+}
+
+/// The image's path for error messages, or its id if it wasn't loaded from a file
+fn describe(handle: &Handle<Image>) -> String {
+    handle
+        .path()
+        .map_or_else(|| format!("{:?}", handle.id()), |path| path.to_string())
+}
+
+/// The smallest square power of two that could hold all the images, packing may still need more
+fn initial_size(sizes: impl Iterator<Item = UVec2>) -> Vec2 {
+    let (area, largest) = sizes.fold((0, 0), |(area, largest), size| {
+        (area + size.x * size.y, largest.max(size.max_element()))
+    });
+    let side = (area as f32).sqrt().ceil() as u32;
+    let side = side.max(largest).next_power_of_two().min(MAX_ATLAS_SIZE);
+    Vec2::splat(side as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+    use bevy::render::render_asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+    fn image(width: u32, height: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    /// Runs `pack` with a packer over the images and atlases of `world`
+    fn with_packer<T>(world: &mut World, pack: impl FnOnce(&mut AtlasPacker) -> T) -> T {
+        let mut state = SystemState::<AtlasPacker>::new(world);
+        let mut packer = state.get_mut(world);
+        pack(&mut packer)
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<Assets<TextureAtlasLayout>>();
+        world
+    }
+
+    #[test]
+    fn starts_at_the_smallest_square_power_of_two() {
+        let four_tiles = [UVec2::splat(64); 4];
+        assert_eq!(initial_size(four_tiles.into_iter()), Vec2::splat(128.0));
+        let five_tiles = [UVec2::splat(64); 5];
+        assert_eq!(initial_size(five_tiles.into_iter()), Vec2::splat(256.0));
+        let wide = [UVec2::new(300, 20), UVec2::splat(16)];
+        assert_eq!(initial_size(wide.into_iter()), Vec2::splat(512.0));
+        let huge = [UVec2::splat(5000)];
+        assert_eq!(
+            initial_size(huge.into_iter()),
+            Vec2::splat(MAX_ATLAS_SIZE as f32)
+        );
+    }
+
+    #[test]
+    fn packs_every_image() {
+        let mut world = world();
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let sources = [images.add(image(32, 32)), images.add(image(64, 16))];
+        let other = images.add(image(8, 8));
+
+        let atlas = with_packer(&mut world, |packer| packer.pack(&sources)).unwrap();
+        let first = atlas.index(&sources[0]).unwrap();
+        let second = atlas.index(&sources[1]).unwrap();
+        assert_ne!(first, second);
+        assert!(matches!(atlas.index(&other), Err(AtlasError::NotPacked(_))));
+        let layout = world
+            .resource::<Assets<TextureAtlasLayout>>()
+            .get(&atlas.layout);
+        assert_eq!(layout.map(|layout| layout.textures.len()), Some(2));
+    }
+
+    #[test]
+    fn rejects_an_empty_group() {
+        let mut world = world();
+        let result = with_packer(&mut world, |packer| packer.pack(&[]));
+        assert!(matches!(result, Err(AtlasError::Empty)));
+    }
+
+    #[test]
+    fn rejects_images_that_are_not_loaded() {
+        let mut world = world();
+        let loaded = world.resource_mut::<Assets<Image>>().add(image(16, 16));
+        let missing = Handle::<Image>::weak_from_u128(0x0A71A5);
+        let result = with_packer(&mut world, |packer| packer.pack(&[loaded, missing]));
+        assert!(matches!(result, Err(AtlasError::NotLoaded(_))));
+    }
+
+    #[test]
+    fn rejects_images_larger_than_the_largest_atlas() {
+        let mut world = world();
+        let wide = world
+            .resource_mut::<Assets<Image>>()
+            .add(image(MAX_ATLAS_SIZE + 1, 1));
+        let result = with_packer(&mut world, |packer| packer.pack(&[wide]));
+        assert!(matches!(result, Err(AtlasError::TooLarge { .. })));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::audio::Ambience;
+use crate::player::Player;
+use crate::settings::Settings;
+use crate::time_of_day::{DayPhase, TimeOfDay};
+use bevy::math::bounding::Aabb2d;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_kira_audio::prelude::*;
+use std::time::Duration;
+
+/// Volume changes smaller than this aren't sent to kira
+const MIN_VOLUME_CHANGE: f64 = 0.01;
+/// Smooths volume changes between frames
+const UPDATE_TWEEN: Duration = Duration::from_millis(250);
+
+pub(super) struct AmbiencePlugin;
+
+impl Plugin for AmbiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ZoneInstances>().add_systems(
+            Update,
+            (start_zones, stop_removed_zones, update_zones).chain(),
+        );
+    }
+}
+
+/// A region of the yard with its own background sound, like birds over the grass
+/// The sound loops on the ambience channel and is heard while the player is inside the region,
+/// fading out over `fade_distance` once they leave it
+#[derive(Component)]
+pub struct AmbientZone {
+    pub sound: Handle<AudioSource>,
+    pub volume: f64,
+    pub region: Aabb2d,
+    pub fade_distance: f32,
+    /// The times of day the zone can be heard, all day if empty
+    pub phases: Vec<DayPhase>,
+}
+
+impl AmbientZone {
+    /// How loud the zone is for a player at `position`, from 0 to 1
+    fn gain(&self, position: Vec2, phase: DayPhase) -> f64 {
+        if !self.phases.is_empty() && !self.phases.contains(&phase) {
+            return 0.0;
+        }
+        let distance = self.region.closest_point(position).distance(position);
+        if self.fade_distance <= 0.0 {
+            return if distance == 0.0 { 1.0 } else { 0.0 };
+        }
+        (1.0 - distance / self.fade_distance).clamp(0.0, 1.0) as f64
+    }
+}
+
+struct ZoneInstance {
+    instance: Handle<AudioInstance>,
+    /// The volume last sent to kira
+    volume: f64,
+}
+
+/// The looping instance of every zone, kept here so it can be stopped after the zone is gone
+#[derive(Resource, Default)]
+struct ZoneInstances(HashMap<Entity, ZoneInstance>);
+
+fn start_zones(
+    mut zone_instances: ResMut<ZoneInstances>,
+    zones: Query<(Entity, &AmbientZone), Added<AmbientZone>>,
+    ambience: Res<AudioChannel<Ambience>>,
+) {
+    for (entity, zone) in &zones {
+        // Starts silent, `update_zones` fades it in if the player is close enough
+        let instance = ambience
+            .play(zone.sound.clone())
+            .looped()
+            .with_volume(0.0)
+            .handle();
+        zone_instances.0.insert(
+            entity,
+            ZoneInstance {
+                instance,
+                volume: 0.0,
+            },
+        );
+    }
+}
+
+fn stop_removed_zones(
+    mut zone_instances: ResMut<ZoneInstances>,
+    mut removed: RemovedComponents<AmbientZone>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    for entity in removed.read() {
+        let Some(zone) = zone_instances.0.remove(&entity) else {
+            continue;
+        };
+        if let Some(instance) = instances.get_mut(&zone.instance) {
+            instance.stop(AudioTween::linear(UPDATE_TWEEN));
+        }
+    }
+}
+
+fn update_zones(
+    mut zone_instances: ResMut<ZoneInstances>,
+    zones: Query<(Entity, &AmbientZone)>,
+    player: Query<&Transform, With<Player>>,
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<Settings>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    // Without a player every zone fades out, e.g. after leaving the game for the menu
+    let position = player
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+    for (entity, zone) in &zones {
+        let Some(zone_instance) = zone_instances.0.get_mut(&entity) else {
+            continue;
+        };
+        let gain = position.map_or(0.0, |position| zone.gain(position, time_of_day.phase()));
+        let volume = zone.volume * gain * settings.sfx_volume();
+        // Small steps are skipped, but fading all the way out always goes through
+        let change = (volume - zone_instance.volume).abs();
+        if change == 0.0 || (change < MIN_VOLUME_CHANGE && volume != 0.0) {
+            continue;
+        }
+        let Some(instance) = instances.get_mut(&zone_instance.instance) else {
+            continue;
+        };
+        instance.set_volume(volume, AudioTween::linear(UPDATE_TWEEN));
+        zone_instance.volume = volume;
+    }
+}
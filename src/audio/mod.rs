@@ -0,0 +1,50 @@
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+mod ambience;
+mod music;
+mod sfx;
+mod spatial;
+
+pub use ambience::AmbientZone;
+pub use music::{Playlists, Soundtrack};
+pub use sfx::{PlaySfx, SfxId};
+pub use spatial::{AudioListener, Falloff, FalloffCurve, SpatialEmitter};
+
+pub struct InternalAudioPlugin;
+
+// This plugin is responsible to control the game audio
+// Music, ambience and sound effects play on their own channels so their volumes are set separately
+// Which music plays is decided by the playlists in `assets/audio/soundtrack.playlists.ron`
+// Sound effects are played by sending `PlaySfx` events
+// Areas of the yard add their own ambience through `AmbientZone`s
+impl Plugin for InternalAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            AudioPlugin,
+            ambience::AmbiencePlugin,
+            music::MusicPlugin,
+            sfx::SfxPlugin,
+            spatial::SpatialAudioPlugin,
+        ))
+        .add_audio_channel::<Music>()
+        .add_audio_channel::<Ambience>()
+        .add_audio_channel::<Sfx>()
+        .add_systems(Update, follow_game_state.run_if(state_changed::<GameState>));
+    }
+}
+
+#[derive(Resource)]
+pub struct Music;
+
+/// Looping background sounds of a place, like wind or traffic
+#[derive(Resource)]
+pub struct Ambience;
+
+#[derive(Resource)]
+pub struct Sfx;
+
+pub(crate) fn follow_game_state(state: Res<State<GameState>>, mut soundtrack: ResMut<Soundtrack>) {
+    soundtrack.playlist = state.get().playlist().map(str::to_owned);
+}
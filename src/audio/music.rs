@@ -0,0 +1,330 @@
+use crate::audio::{Ambience, Music};
+use crate::loading::DataAssets;
+use crate::ron_asset::RonLoaderError;
+use crate::settings::Settings;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
+use bevy_kira_audio::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+pub(super) struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Playlists>()
+            .register_asset_loader(PlaylistsLoader)
+            .init_resource::<Soundtrack>()
+            .init_resource::<MusicPlayer>()
+            .add_systems(
+                Update,
+                (
+                    switch_playlist.run_if(resource_exists::<DataAssets>),
+                    play_next_track,
+                    set_music_volumes.run_if(resource_changed::<Settings>),
+                )
+                    .chain(),
+            );
+
+        #[cfg(feature = "dev")]
+        {
+            app.add_systems(
+                Update,
+                restart_changed_tracks
+                    .before(play_next_track)
+                    .run_if(resource_exists::<DataAssets>),
+            );
+        }
+    }
+}
+
+/// Maps names to playlists, see `assets/audio/soundtrack.playlists.ron`
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Playlists {
+    /// Seconds the old and new playlist overlap when switching between them
+    pub crossfade: f32,
+    pub playlists: HashMap<String, Playlist>,
+}
+
+#[derive(Deserialize)]
+pub struct Playlist {
+    /// Played in order and then from the start again, a single track loops seamlessly
+    #[serde(default)]
+    pub music: Vec<String>,
+    #[serde(default = "full_volume")]
+    pub music_volume: f64,
+    #[serde(default)]
+    pub ambience: Option<String>,
+    #[serde(default = "full_volume")]
+    pub ambience_volume: f64,
+    #[serde(skip)]
+    music_handles: Vec<Handle<AudioSource>>,
+    #[serde(skip)]
+    ambience_handle: Option<Handle<AudioSource>>,
+}
+
+fn full_volume() -> f64 {
+    1.0
+}
+
+/// The playlist that should be heard, changing it crossfades to the new one
+/// Game states set this when they are entered, areas can override it while the game is played
+#[derive(Resource, Default)]
+pub struct Soundtrack {
+    pub playlist: Option<String>,
+}
+
+struct Track {
+    instance: Handle<AudioInstance>,
+    /// The playlist's volume for this track, before the settings are applied
+    volume: f64,
+}
+
+#[derive(Resource, Default)]
+struct MusicPlayer {
+    playlist: Option<String>,
+    next_track: usize,
+    music: Option<Track>,
+    ambience: Option<Track>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn switch_playlist(
+    soundtrack: Res<Soundtrack>,
+    data: Res<DataAssets>,
+    playlists: Res<Assets<Playlists>>,
+    settings: Res<Settings>,
+    mut player: ResMut<MusicPlayer>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    music: Res<AudioChannel<Music>>,
+    ambience: Res<AudioChannel<Ambience>>,
+) {
+    if player.playlist == soundtrack.playlist {
+        return;
+    }
+    let Some(playlists) = playlists.get(&data.playlists) else {
+        return;
+    };
+    let fade = AudioTween::linear(Duration::from_secs_f32(playlists.crossfade));
+    for track in [player.music.take(), player.ambience.take()]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(instance) = instances.get_mut(&track.instance) {
+            instance.stop(fade.clone());
+        }
+    }
+    player.playlist = soundtrack.playlist.clone();
+    player.next_track = 0;
+    let Some(name) = &soundtrack.playlist else {
+        return;
+    };
+    let Some(playlist) = playlists.playlists.get(name) else {
+        warn!("There is no playlist named '{name}', the music stops");
+        return;
+    };
+    if let Some(handle) = &playlist.ambience_handle {
+        let volume = playlist.ambience_volume;
+        player.ambience = Some(Track {
+            instance: ambience
+                .play(handle.clone())
+                .looped()
+                .with_volume(volume * settings.sfx_volume())
+                .fade_in(fade.clone())
+                .handle(),
+            volume,
+        });
+    }
+    player.music = start_track(&mut player, playlist, &music, &settings, fade);
+}
+
+/// Starts the next track of the playlist once the current one ended
+fn play_next_track(
+    data: Option<Res<DataAssets>>,
+    playlists: Res<Assets<Playlists>>,
+    settings: Res<Settings>,
+    mut player: ResMut<MusicPlayer>,
+    instances: Res<Assets<AudioInstance>>,
+    music: Res<AudioChannel<Music>>,
+) {
+    let Some(track) = &player.music else {
+        return;
+    };
+    if !matches!(
+        instances.get(&track.instance).map(AudioInstance::state),
+        Some(PlaybackState::Stopped)
+    ) {
+        return;
+    }
+    let Some(playlist) = data
+        .and_then(|data| playlists.get(&data.playlists))
+        .zip(player.playlist.as_ref())
+        .and_then(|(playlists, name)| playlists.playlists.get(name))
+    else {
+        return;
+    };
+    player.music = start_track(
+        &mut player,
+        playlist,
+        &music,
+        &settings,
+        AudioTween::default(),
+    );
+}
+
+fn start_track(
+    player: &mut MusicPlayer,
+    playlist: &Playlist,
+    music: &AudioChannel<Music>,
+    settings: &Settings,
+    fade_in: AudioTween,
+) -> Option<Track> {
+    if playlist.music_handles.is_empty() {
+        return None;
+    }
+    let index = player.next_track % playlist.music_handles.len();
+    player.next_track = index + 1;
+    let mut command = music.play(playlist.music_handles[index].clone());
+    if playlist.music_handles.len() == 1 {
+        command.looped();
+    }
+    Some(Track {
+        instance: command
+            .with_volume(playlist.music_volume * settings.music_volume())
+            .fade_in(fade_in)
+            .handle(),
+        volume: playlist.music_volume,
+    })
+}
+
+fn set_music_volumes(
+    settings: Res<Settings>,
+    player: Res<MusicPlayer>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    // Instance volumes instead of channel volumes, those would undo the playlists' mix
+    // Ambience follows the sound effects volume, it is part of the world rather than the score
+    let tracks = [
+        (&player.music, settings.music_volume()),
+        (&player.ambience, settings.sfx_volume()),
+    ];
+    for (track, volume) in tracks {
+        let Some(track) = track else {
+            continue;
+        };
+        if let Some(instance) = instances.get_mut(&track.instance) {
+            instance.set_volume(track.volume * volume, AudioTween::default());
+        }
+    }
+}
+
+/// Plays the tracks again whose files changed on disk, the music from the start of its track
+#[cfg(feature = "dev")]
+fn restart_changed_tracks(
+    mut events: EventReader<AssetEvent<AudioSource>>,
+    data: Res<DataAssets>,
+    playlists: Res<Assets<Playlists>>,
+    settings: Res<Settings>,
+    mut player: ResMut<MusicPlayer>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    ambience: Res<AudioChannel<Ambience>>,
+) {
+    let changed: Vec<AssetId<AudioSource>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
+    let Some(playlist) = playlists
+        .get(&data.playlists)
+        .zip(player.playlist.as_ref())
+        .and_then(|(playlists, name)| playlists.playlists.get(name))
+    else {
+        return;
+    };
+
+    // The track that is playing was started last, stopping it lets `play_next_track` start it again
+    let playing = player.next_track.checked_sub(1);
+    let music = playing.and_then(|index| playlist.music_handles.get(index));
+    if let (Some(index), Some(handle)) = (playing, music) {
+        if changed.contains(&handle.id()) {
+            if let Some(instance) = player
+                .music
+                .as_ref()
+                .and_then(|track| instances.get_mut(&track.instance))
+            {
+                instance.stop(AudioTween::default());
+            }
+            player.next_track = index;
+        }
+    }
+
+    let Some(handle) = &playlist.ambience_handle else {
+        return;
+    };
+    if !changed.contains(&handle.id()) {
+        return;
+    }
+    if let Some(track) = player.ambience.take() {
+        if let Some(instance) = instances.get_mut(&track.instance) {
+            instance.stop(AudioTween::default());
+        }
+        player.ambience = Some(Track {
+            instance: ambience
+                .play(handle.clone())
+                .looped()
+                .with_volume(track.volume * settings.sfx_volume())
+                .handle(),
+            volume: track.volume,
+        });
+    }
+}
+
+/// Reads playlists like `RonAssetLoader` and loads their tracks as dependencies,
+/// so the music is ready once the playlists are
+struct PlaylistsLoader;
+
+impl AssetLoader for PlaylistsLoader {
+    type Asset = Playlists;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Playlists, RonLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(RonLoaderError::Io)?;
+            let mut playlists: Playlists =
+                ron::de::from_bytes(&bytes).map_err(RonLoaderError::Ron)?;
+            for playlist in playlists.playlists.values_mut() {
+                playlist.music_handles = playlist
+                    .music
+                    .iter()
+                    .map(|path| load_context.load(path))
+                    .collect();
+                playlist.ambience_handle = playlist
+                    .ambience
+                    .as_ref()
+                    .map(|path| load_context.load(path));
+            }
+            Ok(playlists)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["playlists.ron"]
+    }
+}
@@ -0,0 +1,146 @@
+use crate::audio::spatial::{spatialize, AudioListener, EmitterInstances, Falloff};
+use crate::audio::Sfx;
+use crate::loading::AudioAssets;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_kira_audio::prelude::*;
+use rand::prelude::*;
+
+/// More sounds than this at once turn into noise, new ones are dropped until some have ended
+/// Audible `SpatialEmitter`s count against it too
+pub(super) const MAX_PLAYING: usize = 8;
+/// Seconds a sound may take to start before it no longer counts as playing
+const START_GRACE: f32 = 0.5;
+
+pub(super) struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySfx>()
+            .init_resource::<SfxPlayer>()
+            .add_systems(
+                Update,
+                play_sfx.run_if(resource_exists::<AudioAssets>.and_then(on_event::<PlaySfx>())),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SfxId {
+    Flying,
+    Galaxie,
+}
+
+impl SfxId {
+    fn handle(&self, audio_assets: &AudioAssets) -> Handle<AudioSource> {
+        match self {
+            SfxId::Flying => audio_assets.flying.clone(),
+            SfxId::Galaxie => audio_assets.galaxie.clone(),
+        }
+    }
+
+    /// Seconds before the same sound can be played again
+    fn cooldown(&self) -> f32 {
+        match self {
+            SfxId::Flying => 0.3,
+            SfxId::Galaxie => 1.0,
+        }
+    }
+
+    /// The playback rate varies by up to this much in both directions, so repeats sound less alike
+    fn pitch_variance(&self) -> f64 {
+        match self {
+            SfxId::Flying => 0.1,
+            SfxId::Galaxie => 0.03,
+        }
+    }
+}
+
+/// Send this from any system to play a sound effect
+#[derive(Event)]
+pub struct PlaySfx {
+    pub id: SfxId,
+    /// Multiplied with the sound effect volume from the settings
+    pub volume: f32,
+    /// Where in the world the sound comes from, it is panned and attenuated relative to the
+    /// `AudioListener` when it starts. `None` plays it in the middle at full volume
+    pub position: Option<Vec2>,
+}
+
+impl PlaySfx {
+    pub fn new(id: SfxId) -> Self {
+        PlaySfx {
+            id,
+            volume: 1.0,
+            position: None,
+        }
+    }
+
+    pub fn at(mut self, position: Vec2) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+struct SfxPlayer {
+    /// When each sound was played last, in seconds since startup
+    last_played: HashMap<SfxId, f32>,
+    /// Handles of sounds that may still be playing, with the time they were sent to kira
+    playing: Vec<(Handle<AudioInstance>, f32)>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_sfx(
+    mut events: EventReader<PlaySfx>,
+    mut player: ResMut<SfxPlayer>,
+    time: Res<Time>,
+    audio_assets: Res<AudioAssets>,
+    settings: Res<Settings>,
+    instances: Res<Assets<AudioInstance>>,
+    emitters: Res<EmitterInstances>,
+    sfx: Res<AudioChannel<Sfx>>,
+    listener: Query<&GlobalTransform, With<AudioListener>>,
+) {
+    let now = time.elapsed_seconds();
+    // Instances only exist while kira plays them, queued sounds get a moment to start
+    player
+        .playing
+        .retain(|(handle, started)| match instances.get(handle) {
+            Some(instance) => instance.state() != PlaybackState::Stopped,
+            None => now - started < START_GRACE,
+        });
+    let listener = listener
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation().truncate());
+    let mut rng = thread_rng();
+    // Every event is read, the ones over the cap are dropped instead of played next frame
+    for event in events.read() {
+        if player.playing.len() + emitters.audible >= MAX_PLAYING {
+            continue;
+        }
+        if player
+            .last_played
+            .get(&event.id)
+            .is_some_and(|last| now - last < event.id.cooldown())
+        {
+            continue;
+        }
+        player.last_played.insert(event.id, now);
+
+        let variance = event.id.pitch_variance();
+        let (panning, gain) = match (event.position, listener) {
+            (Some(position), Some(listener)) => spatialize(listener, position, &Falloff::default()),
+            _ => (0.5, 1.0),
+        };
+        let handle = sfx
+            .play(event.id.handle(&audio_assets))
+            .with_volume(event.volume as f64 * gain * settings.sfx_volume())
+            .with_playback_rate(1.0 + rng.gen_range(-variance..=variance))
+            .with_panning(panning)
+            .handle();
+        player.playing.push((handle, now));
+    }
+}
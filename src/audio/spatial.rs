@@ -0,0 +1,182 @@
+use crate::audio::sfx::MAX_PLAYING;
+use crate::audio::Sfx;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy::utils::HashMap;
+use bevy_kira_audio::prelude::*;
+use std::time::Duration;
+
+/// How far left or right of the listener a sound is panned all the way to one side
+const PAN_DISTANCE: f32 = 600.0;
+/// Smooths volume and panning changes between frames
+const UPDATE_TWEEN: Duration = Duration::from_millis(50);
+
+pub(super) struct SpatialAudioPlugin;
+
+impl Plugin for SpatialAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EmitterInstances>().add_systems(
+            PostUpdate,
+            (start_emitters, stop_removed_emitters, update_emitters)
+                .chain()
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Sounds are heard from here, put on the camera that follows the player
+#[derive(Component)]
+pub struct AudioListener;
+
+/// How a sound gets quieter with its distance to the listener
+#[derive(Clone, Copy, Debug)]
+pub struct Falloff {
+    /// Up to this distance the sound plays at full volume
+    pub min_distance: f32,
+    /// From this distance on the sound can't be heard anymore
+    pub max_distance: f32,
+    pub curve: FalloffCurve,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FalloffCurve {
+    /// Stays loud for longer close by and drops off quickly towards the end
+    Quadratic,
+    /// Falls off with one over the distance like sound in the open, loud close by and a long tail
+    Inverse,
+}
+
+impl Default for Falloff {
+    fn default() -> Self {
+        Falloff {
+            min_distance: 100.0,
+            max_distance: 1000.0,
+            curve: FalloffCurve::Inverse,
+        }
+    }
+}
+
+impl Falloff {
+    pub fn gain(&self, distance: f32) -> f64 {
+        if distance <= self.min_distance {
+            return 1.0;
+        }
+        if distance >= self.max_distance {
+            return 0.0;
+        }
+        let gain = match self.curve {
+            FalloffCurve::Quadratic => {
+                let t = (distance - self.min_distance) / (self.max_distance - self.min_distance);
+                1.0 - t * t
+            }
+            FalloffCurve::Inverse => {
+                // Shifted down so it still reaches silence at the max distance
+                let at_max = self.min_distance / self.max_distance;
+                (self.min_distance / distance - at_max) / (1.0 - at_max)
+            }
+        };
+        gain as f64
+    }
+}
+
+/// Panning and gain of a sound at `position` for a listener at `listener`
+pub(super) fn spatialize(listener: Vec2, position: Vec2, falloff: &Falloff) -> (f64, f64) {
+    let panning = 0.5 + ((position.x - listener.x) / PAN_DISTANCE).clamp(-1.0, 1.0) as f64 / 2.0;
+    (panning, falloff.gain(listener.distance(position)))
+}
+
+/// Loops a sound on the sound effects channel for as long as the entity has this component
+/// Its volume and panning follow the entity's position relative to the `AudioListener`
+/// Audible emitters take up voices of the sound effects cap, past it the quietest are muted
+#[derive(Component)]
+pub struct SpatialEmitter {
+    pub source: Handle<AudioSource>,
+    pub volume: f64,
+    pub falloff: Falloff,
+}
+
+/// The looping instance of every emitter, kept here so it can be stopped after the emitter is gone
+#[derive(Resource, Default)]
+pub(super) struct EmitterInstances {
+    instances: HashMap<Entity, Handle<AudioInstance>>,
+    /// How many emitters could be heard last frame, they take up voices of the sound effects cap
+    pub(super) audible: usize,
+}
+
+fn start_emitters(
+    mut emitter_instances: ResMut<EmitterInstances>,
+    emitters: Query<(Entity, &SpatialEmitter), Added<SpatialEmitter>>,
+    sfx: Res<AudioChannel<Sfx>>,
+) {
+    for (entity, emitter) in &emitters {
+        // Starts silent, `update_emitters` fades it to the right volume
+        let instance = sfx
+            .play(emitter.source.clone())
+            .looped()
+            .with_volume(0.0)
+            .handle();
+        emitter_instances.instances.insert(entity, instance);
+    }
+}
+
+fn stop_removed_emitters(
+    mut emitter_instances: ResMut<EmitterInstances>,
+    mut removed: RemovedComponents<SpatialEmitter>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    for entity in removed.read() {
+        let Some(handle) = emitter_instances.instances.remove(&entity) else {
+            continue;
+        };
+        if let Some(instance) = instances.get_mut(&handle) {
+            instance.stop(AudioTween::linear(UPDATE_TWEEN));
+        }
+    }
+}
+
+fn update_emitters(
+    mut emitter_instances: ResMut<EmitterInstances>,
+    emitters: Query<(Entity, &SpatialEmitter, &GlobalTransform)>,
+    listener: Query<&GlobalTransform, With<AudioListener>>,
+    settings: Res<Settings>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    let Ok(listener) = listener.get_single() else {
+        return;
+    };
+    let listener = listener.translation().truncate();
+    let mut emitters: Vec<_> = emitters
+        .iter()
+        .map(|(entity, emitter, transform)| {
+            let (panning, gain) = spatialize(
+                listener,
+                transform.translation().truncate(),
+                &emitter.falloff,
+            );
+            (entity, emitter.volume * gain, panning)
+        })
+        .collect();
+    // The loudest emitters get the voices, the rest are muted until they come closer
+    emitters.sort_by(|a, b| b.1.total_cmp(&a.1));
+    emitter_instances.audible = emitters
+        .iter()
+        .take(MAX_PLAYING)
+        .filter(|(_, volume, _)| *volume > 0.0)
+        .count();
+    for (index, (entity, volume, panning)) in emitters.into_iter().enumerate() {
+        let Some(instance) = emitter_instances
+            .instances
+            .get(&entity)
+            .and_then(|handle| instances.get_mut(handle))
+        else {
+            continue;
+        };
+        let volume = if index < MAX_PLAYING { volume } else { 0.0 };
+        instance.set_volume(
+            volume * settings.sfx_volume(),
+            AudioTween::linear(UPDATE_TWEEN),
+        );
+        instance.set_panning(panning, AudioTween::linear(UPDATE_TWEEN));
+    }
+}
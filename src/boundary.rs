@@ -0,0 +1,363 @@
+use crate::broad_phase::BroadPhase;
+use crate::flags::GameFlags;
+use crate::helpe::{character_bounds, Bounding};
+use crate::npc::Npc;
+use crate::player::Player;
+use crate::GameState;
+use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy::utils::{HashMap, HashSet};
+
+const WALL_COLOR: Color = Color::rgb(0.9, 0.2, 0.2);
+const TRIGGER_COLOR: Color = Color::rgb(0.95, 0.85, 0.2);
+
+pub struct BoundaryPlugin;
+
+/// This plugin handles the invisible parts of the world, walls that block the way
+/// and triggers that notice the player and NPCs walking in and out of them
+/// Their shapes are drawn while `ShowBoundaries` is on, F3 toggles it in debug builds
+impl Plugin for BoundaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShowBoundaries>()
+            .init_resource::<TriggerOccupants>()
+            .add_event::<TriggerEntered>()
+            .add_event::<TriggerStayed>()
+            .add_event::<TriggerExited>()
+            .add_systems(
+                Update,
+                (
+                    (detect_triggers, (remember_visits, log_triggers))
+                        .chain()
+                        .run_if(in_state(GameState::Playing)),
+                    draw_boundaries.run_if(|show: Res<ShowBoundaries>| show.0),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                update_volume_bounds.after(TransformSystem::TransformPropagate),
+            );
+
+        #[cfg(debug_assertions)]
+        {
+            app.add_systems(Update, toggle_boundaries);
+        }
+    }
+}
+
+/// Whether the shapes of walls and triggers are drawn
+#[derive(Resource, Default)]
+pub struct ShowBoundaries(pub bool);
+
+/// A box shape centered on the entity, its `Bounding` follows the entity around
+#[derive(Component)]
+pub struct Volume {
+    pub half_size: Vec2,
+}
+
+/// An invisible collider that blocks the way like scenery
+#[derive(Component)]
+pub struct Wall;
+
+impl Wall {
+    /// A wall at `translation` relative to its parent, spawn it as a child of the scenery
+    /// so the player and the navigation grid treat it like any other collider
+    pub fn bundle(translation: Vec3, half_size: Vec2) -> (SpatialBundle, Volume, Bounding, Wall) {
+        (
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+            Volume { half_size },
+            Bounding::new(),
+            Wall,
+        )
+    }
+}
+
+/// An area the player and NPCs walk through, telling who enters, stays in and leaves it
+/// Its shape is its `Bounding`, so a trigger can also take the boxes of a sprite
+#[derive(Component)]
+pub struct Trigger {
+    pub id: String,
+}
+
+impl Trigger {
+    pub fn new(id: &str) -> Self {
+        Trigger { id: id.to_owned() }
+    }
+
+    /// An invisible trigger at `translation` relative to its parent
+    pub fn bundle(
+        id: &str,
+        translation: Vec3,
+        half_size: Vec2,
+    ) -> (SpatialBundle, Volume, Bounding, Trigger) {
+        (
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+            Volume { half_size },
+            Bounding::new(),
+            Trigger::new(id),
+        )
+    }
+}
+
+/// A character walked into a trigger
+#[derive(Event)]
+pub struct TriggerEntered {
+    pub trigger: Entity,
+    pub id: String,
+    /// The player or an NPC
+    pub actor: Entity,
+}
+
+/// A character is still inside a trigger, sent every frame after `TriggerEntered`
+#[derive(Event)]
+pub struct TriggerStayed {
+    pub trigger: Entity,
+    pub id: String,
+    pub actor: Entity,
+}
+
+/// A character left a trigger or the trigger went away under it
+#[derive(Event)]
+pub struct TriggerExited {
+    pub trigger: Entity,
+    pub id: String,
+    pub actor: Entity,
+}
+
+/// The id of every trigger with the characters that were inside it last frame
+/// Kept apart from the triggers so their characters can still be told when a trigger is despawned
+#[derive(Resource, Default)]
+struct TriggerOccupants(HashMap<Entity, (String, HashSet<Entity>)>);
+
+fn update_volume_bounds(
+    mut volumes: Query<(&Volume, &GlobalTransform, &mut Bounding), Changed<GlobalTransform>>,
+) {
+    for (volume, transform, mut bounding) in &mut volumes {
+        bounding.boxes.clear();
+        bounding.boxes.push(Aabb2d::new(
+            transform.translation().truncate(),
+            volume.half_size,
+        ));
+    }
+}
+
+/// Finds the triggers each character overlaps through the broad phase of the colliders
+#[allow(clippy::too_many_arguments)]
+fn detect_triggers(
+    actors: Query<(Entity, &Transform), Or<(With<Player>, With<Npc>)>>,
+    triggers: Query<(Entity, &Trigger, &Bounding)>,
+    mut removed: RemovedComponents<Trigger>,
+    mut occupants: ResMut<TriggerOccupants>,
+    broad_phase: Res<BroadPhase>,
+    mut entered: EventWriter<TriggerEntered>,
+    mut stayed: EventWriter<TriggerStayed>,
+    mut exited: EventWriter<TriggerExited>,
+) {
+    let mut inside: HashMap<Entity, HashSet<Entity>> = HashMap::new();
+    for (actor, transform) in &actors {
+        let bounds = character_bounds(transform.translation);
+        for candidate in broad_phase.nearby(&bounds) {
+            let Ok((trigger, _, bounding)) = triggers.get(candidate) else {
+                continue;
+            };
+            if bounding.boxes.iter().any(|other| other.intersects(&bounds)) {
+                inside.entry(trigger).or_default().insert(actor);
+            }
+        }
+    }
+    // Everyone inside a trigger that went away has left it
+    for entity in removed.read() {
+        let Some((id, before)) = occupants.0.remove(&entity) else {
+            continue;
+        };
+        for actor in before {
+            exited.send(TriggerExited {
+                trigger: entity,
+                id: id.clone(),
+                actor,
+            });
+        }
+    }
+    for (entity, trigger, _) in &triggers {
+        let now = inside.remove(&entity).unwrap_or_default();
+        let before = occupants
+            .0
+            .remove(&entity)
+            .map(|(_, before)| before)
+            .unwrap_or_default();
+        if now.is_empty() && before.is_empty() {
+            continue;
+        }
+        for &actor in before.difference(&now) {
+            exited.send(TriggerExited {
+                trigger: entity,
+                id: trigger.id.clone(),
+                actor,
+            });
+        }
+        for &actor in now.iter() {
+            if before.contains(&actor) {
+                stayed.send(TriggerStayed {
+                    trigger: entity,
+                    id: trigger.id.clone(),
+                    actor,
+                });
+            } else {
+                entered.send(TriggerEntered {
+                    trigger: entity,
+                    id: trigger.id.clone(),
+                    actor,
+                });
+            }
+        }
+        occupants.0.insert(entity, (trigger.id.clone(), now));
+    }
+}
+
+/// The player entering a trigger sets the `visited_<id>` flag, so conversations can ask
+/// where the player has been
+fn remember_visits(
+    mut flags: ResMut<GameFlags>,
+    player: Query<(), With<Player>>,
+    mut entered: EventReader<TriggerEntered>,
+) {
+    for event in entered.read() {
+        if player.contains(event.actor) {
+            flags.set(format!("visited_{}", event.id));
+        }
+    }
+}
+
+fn log_triggers(
+    mut entered: EventReader<TriggerEntered>,
+    mut stayed: EventReader<TriggerStayed>,
+    mut exited: EventReader<TriggerExited>,
+) {
+    for event in entered.read() {
+        debug!(
+            "{:?} entered trigger {} ({:?})",
+            event.actor, event.id, event.trigger
+        );
+    }
+    for event in stayed.read() {
+        trace!(
+            "{:?} is in trigger {} ({:?})",
+            event.actor,
+            event.id,
+            event.trigger
+        );
+    }
+    for event in exited.read() {
+        debug!(
+            "{:?} left trigger {} ({:?})",
+            event.actor, event.id, event.trigger
+        );
+    }
+}
+
+fn draw_boundaries(
+    mut gizmos: Gizmos,
+    walls: Query<&Bounding, With<Wall>>,
+    triggers: Query<&Bounding, With<Trigger>>,
+) {
+    for bounds in walls.iter().flat_map(|bounding| bounding.boxes.iter()) {
+        gizmos.rect_2d(bounds.center(), 0.0, bounds.half_size() * 2.0, WALL_COLOR);
+    }
+    for bounds in triggers.iter().flat_map(|bounding| bounding.boxes.iter()) {
+        gizmos.rect_2d(
+            bounds.center(),
+            0.0,
+            bounds.half_size() * 2.0,
+            TRIGGER_COLOR,
+        );
+    }
+}
+
+#[cfg(debug_assertions)]
+fn toggle_boundaries(keyboard: Res<ButtonInput<KeyCode>>, mut show: ResMut<ShowBoundaries>) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        show.0 = !show.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broad_phase::BroadPhasePlugin;
+
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Entered(Entity),
+        Stayed(Entity),
+        Exited(Entity),
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(BroadPhasePlugin)
+            .init_resource::<TriggerOccupants>()
+            .add_event::<TriggerEntered>()
+            .add_event::<TriggerStayed>()
+            .add_event::<TriggerExited>()
+            .add_systems(Update, detect_triggers);
+        app
+    }
+
+    /// Runs a frame and returns the trigger events it sent, for the trigger with id "gate"
+    fn frame(app: &mut App) -> Vec<Seen> {
+        app.update();
+        let world = &mut app.world;
+        let mut seen = Vec::new();
+        for event in world.resource_mut::<Events<TriggerEntered>>().drain() {
+            assert_eq!(event.id, "gate");
+            seen.push(Seen::Entered(event.actor));
+        }
+        for event in world.resource_mut::<Events<TriggerStayed>>().drain() {
+            assert_eq!(event.id, "gate");
+            seen.push(Seen::Stayed(event.actor));
+        }
+        for event in world.resource_mut::<Events<TriggerExited>>().drain() {
+            assert_eq!(event.id, "gate");
+            seen.push(Seen::Exited(event.actor));
+        }
+        seen
+    }
+
+    fn walk_to(app: &mut App, actor: Entity, x: f32) {
+        app.world.get_mut::<Transform>(actor).unwrap().translation.x = x;
+    }
+
+    #[test]
+    fn tells_who_enters_stays_and_leaves() {
+        let mut app = app();
+        let mut gate = Bounding::new();
+        gate.boxes.push(Aabb2d::new(Vec2::ZERO, Vec2::splat(50.0)));
+        let trigger = app.world.spawn((gate, Trigger::new("gate"))).id();
+        let player = app
+            .world
+            .spawn((Transform::from_xyz(300.0, 0.0, 0.0), Player))
+            .id();
+        let npc = app
+            .world
+            .spawn((Transform::from_xyz(-300.0, 0.0, 0.0), Npc))
+            .id();
+        // The broad phase picks the trigger up at the end of the first frame
+        assert_eq!(frame(&mut app), []);
+
+        walk_to(&mut app, player, 0.0);
+        assert_eq!(frame(&mut app), [Seen::Entered(player)]);
+        assert_eq!(frame(&mut app), [Seen::Stayed(player)]);
+
+        walk_to(&mut app, npc, 40.0);
+        assert_eq!(frame(&mut app), [Seen::Entered(npc), Seen::Stayed(player)]);
+
+        walk_to(&mut app, player, 300.0);
+        assert_eq!(frame(&mut app), [Seen::Stayed(npc), Seen::Exited(player)]);
+        assert_eq!(frame(&mut app), [Seen::Stayed(npc)]);
+
+        // Everyone still inside leaves a trigger that is despawned
+        app.world.despawn(trigger);
+        assert_eq!(frame(&mut app), [Seen::Exited(npc)]);
+        assert_eq!(frame(&mut app), []);
+    }
+}
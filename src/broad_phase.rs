@@ -0,0 +1,136 @@
+use crate::helpe::Bounding;
+use bevy::ecs::query::QueryFilter;
+use bevy::math::bounding::{Aabb2d, IntersectsVolume};
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy::utils::{HashMap, HashSet};
+
+/// Edge length of one broad-phase cell in world units, a bit bigger than most scenery
+const CELL_SIZE: f32 = 256.0;
+
+pub struct BroadPhasePlugin;
+
+/// This plugin sorts every `Bounding` into a coarse grid, so collision and trigger checks
+/// only look at the boxes around them instead of all boxes in the world
+impl Plugin for BroadPhasePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BroadPhase>().add_systems(
+            PostUpdate,
+            index_boundings.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// The cells every `Bounding` overlaps, updated when its boxes change
+#[derive(Resource, Default)]
+pub struct BroadPhase {
+    cells: HashMap<IVec2, Vec<Entity>>,
+    entries: HashMap<Entity, Vec<IVec2>>,
+}
+
+impl BroadPhase {
+    pub fn insert(&mut self, entity: Entity, boxes: &[Aabb2d]) {
+        self.remove(entity);
+        let cells: HashSet<IVec2> = boxes.iter().flat_map(covered_cells).collect();
+        for &cell in cells.iter() {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+        self.entries.insert(entity, cells.into_iter().collect());
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some(cells) = self.entries.remove(&entity) else {
+            return;
+        };
+        for cell in cells {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|&other| other != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// Entities whose boxes may overlap `bounds`, each of them once
+    pub fn nearby(&self, bounds: &Aabb2d) -> HashSet<Entity> {
+        covered_cells(bounds)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Whether `bounds` overlaps a box of any `Bounding` in `boundings`
+    pub fn collides<F: QueryFilter>(
+        &self,
+        bounds: &Aabb2d,
+        boundings: &Query<&Bounding, F>,
+    ) -> bool {
+        self.nearby(bounds).into_iter().any(|entity| {
+            boundings
+                .get(entity)
+                .is_ok_and(|bounding| bounding.boxes.iter().any(|other| other.intersects(bounds)))
+        })
+    }
+}
+
+fn covered_cells(bounds: &Aabb2d) -> impl Iterator<Item = IVec2> {
+    let min = (bounds.min / CELL_SIZE).floor().as_ivec2();
+    let max = (bounds.max / CELL_SIZE).floor().as_ivec2();
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+}
+
+fn index_boundings(
+    mut broad_phase: ResMut<BroadPhase>,
+    changed: Query<(Entity, &Bounding), Changed<Bounding>>,
+    mut removed: RemovedComponents<Bounding>,
+) {
+    for entity in removed.read() {
+        broad_phase.remove(entity);
+    }
+    for (entity, bounding) in &changed {
+        broad_phase.insert(entity, &bounding.boxes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(center: Vec2, half_size: f32) -> Aabb2d {
+        Aabb2d::new(center, Vec2::splat(half_size))
+    }
+
+    #[test]
+    fn finds_boxes_across_cell_borders() {
+        let mut broad_phase = BroadPhase::default();
+        let (on_border, right, below) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        // Straddles the border between the cells left and right of x = 256
+        broad_phase.insert(on_border, &[square(Vec2::new(256.0, 100.0), 16.0)]);
+        broad_phase.insert(right, &[square(Vec2::new(700.0, 100.0), 16.0)]);
+        // Below y = 0, in the row of cells with negative coordinates
+        broad_phase.insert(below, &[square(Vec2::new(100.0, -20.0), 8.0)]);
+
+        let left_cell = broad_phase.nearby(&square(Vec2::new(100.0, 100.0), 4.0));
+        assert_eq!(left_cell, HashSet::from_iter([on_border]));
+        let right_cell = broad_phase.nearby(&square(Vec2::new(400.0, 100.0), 4.0));
+        assert_eq!(right_cell, HashSet::from_iter([on_border]));
+        let across = broad_phase.nearby(&Aabb2d {
+            min: Vec2::new(100.0, 100.0),
+            max: Vec2::new(600.0, 100.0),
+        });
+        assert_eq!(across, HashSet::from_iter([on_border, right]));
+        let around_zero = broad_phase.nearby(&square(Vec2::new(100.0, 0.0), 4.0));
+        assert_eq!(around_zero, HashSet::from_iter([on_border, below]));
+
+        broad_phase.remove(on_border);
+        assert!(broad_phase
+            .nearby(&square(Vec2::new(100.0, 100.0), 4.0))
+            .is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::actions::{Actions, MoveTarget};
+use crate::audio::{PlaySfx, SfxId};
+use crate::flags::GameFlags;
+use crate::ron_asset::RonAssetPlugin;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+mod ui;
+
+pub struct DialoguePlugin;
+
+/// This plugin runs conversations authored in `assets/dialogue/*.dialogue.ron`
+/// While a conversation is open the `ActiveDialogue` resource exists and the player can't move
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<Dialogue>::new(&["dialogue.ron"]))
+            .init_resource::<GameFlags>()
+            .add_event::<StartDialogue>()
+            .add_event::<DialogueEnded>()
+            .add_systems(
+                Update,
+                (
+                    start_dialogue,
+                    (choose_with_buttons, navigate_dialogue)
+                        .run_if(resource_exists::<ActiveDialogue>),
+                    ui::refresh_dialogue_box,
+                    ui::type_dialogue_text,
+                    ui::highlight_selected_choice,
+                )
+                    .chain()
+                    .after(crate::actions::set_button_actions)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), close_dialogue);
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Dialogue {
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+#[derive(Deserialize)]
+pub struct DialogueNode {
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub text: String,
+    /// Offered after the text is shown, choices whose condition fails are hidden
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+    /// The node to continue with if there are no choices, the conversation ends without one
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub set_flags: Vec<String>,
+    #[serde(default)]
+    pub clear_flags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Choice {
+    pub text: String,
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub condition: Option<Condition>,
+}
+
+#[derive(Deserialize)]
+pub enum Condition {
+    Set(String),
+    NotSet(String),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn holds(&self, flags: &GameFlags) -> bool {
+        match self {
+            Condition::Set(flag) => flags.is_set(flag),
+            Condition::NotSet(flag) => !flags.is_set(flag),
+            Condition::All(conditions) => conditions.iter().all(|c| c.holds(flags)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.holds(flags)),
+        }
+    }
+}
+
+/// Send this to open a conversation, it is ignored while another one is running
+#[derive(Event)]
+pub struct StartDialogue {
+    pub dialogue: Handle<Dialogue>,
+}
+
+#[derive(Event)]
+pub struct DialogueEnded;
+
+#[derive(Resource)]
+pub struct ActiveDialogue {
+    dialogue: Handle<Dialogue>,
+    node: String,
+    /// Indices into the node's choices that passed their conditions
+    choices: Vec<usize>,
+    selected: usize,
+}
+
+impl ActiveDialogue {
+    fn enter(
+        dialogue: Handle<Dialogue>,
+        asset: &Dialogue,
+        node: &str,
+        flags: &mut GameFlags,
+    ) -> Option<Self> {
+        let Some(dialogue_node) = asset.nodes.get(node) else {
+            warn!("Dialogue node '{node}' does not exist");
+            return None;
+        };
+        for flag in dialogue_node.set_flags.iter() {
+            flags.set(flag.clone());
+        }
+        for flag in dialogue_node.clear_flags.iter() {
+            flags.clear(flag);
+        }
+        let choices = dialogue_node
+            .choices
+            .iter()
+            .enumerate()
+            .filter(|(_, choice)| choice.condition.as_ref().is_none_or(|c| c.holds(flags)))
+            .map(|(index, _)| index)
+            .collect();
+        Some(ActiveDialogue {
+            dialogue,
+            node: node.to_owned(),
+            choices,
+            selected: 0,
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_dialogue(
+    mut commands: Commands,
+    mut events: EventReader<StartDialogue>,
+    active: Option<Res<ActiveDialogue>>,
+    dialogues: Res<Assets<Dialogue>>,
+    mut flags: ResMut<GameFlags>,
+    mut actions: ResMut<Actions>,
+    mut move_target: ResMut<MoveTarget>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    if active.is_some() {
+        return;
+    }
+    let Some(asset) = dialogues.get(&event.dialogue) else {
+        warn!("Tried to start a dialogue that is not loaded");
+        return;
+    };
+    if let Some(dialogue) =
+        ActiveDialogue::enter(event.dialogue.clone(), asset, &asset.start, &mut flags)
+    {
+        actions.player_movement = None;
+        move_target.cancel();
+        sfx.send(PlaySfx {
+            volume: 0.6,
+            ..PlaySfx::new(SfxId::Galaxie)
+        });
+        commands.insert_resource(dialogue);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn navigate_dialogue(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touch_input: Res<Touches>,
+    mut active: ResMut<ActiveDialogue>,
+    dialogues: Res<Assets<Dialogue>>,
+    mut flags: ResMut<GameFlags>,
+    mut typewriter: Query<&mut ui::Typewriter>,
+    choice_buttons: Query<&Interaction, With<ui::ChoiceButton>>,
+    mut ended: EventWriter<DialogueEnded>,
+) {
+    // The input that opened the conversation shouldn't also skip its first line
+    if active.is_added() {
+        return;
+    }
+    if actions.cancel {
+        end_dialogue(&mut commands, &mut ended);
+        return;
+    }
+    if !active.choices.is_empty() {
+        let count = active.choices.len();
+        if actions.select_previous {
+            active.selected = (active.selected + count - 1) % count;
+        }
+        if actions.select_next {
+            active.selected = (active.selected + 1) % count;
+        }
+    }
+
+    let pointer_pressed = (mouse_input.just_pressed(MouseButton::Left)
+        || touch_input.any_just_pressed())
+        && !choice_buttons
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed);
+    if !actions.interact && !pointer_pressed {
+        return;
+    }
+    // The first press reveals the whole line, the next one moves on
+    if let Ok(mut typewriter) = typewriter.get_single_mut() {
+        if !typewriter.is_finished() {
+            typewriter.finish();
+            return;
+        }
+    }
+    let Some(asset) = dialogues.get(&active.dialogue) else {
+        end_dialogue(&mut commands, &mut ended);
+        return;
+    };
+    let Some(node) = asset.nodes.get(&active.node) else {
+        end_dialogue(&mut commands, &mut ended);
+        return;
+    };
+    let next = if active.choices.is_empty() {
+        node.next.clone()
+    } else if pointer_pressed {
+        // Pointers pick choices by pressing their buttons directly
+        return;
+    } else {
+        node.choices[active.choices[active.selected]].next.clone()
+    };
+    go_to(
+        &mut commands,
+        &mut active,
+        asset,
+        next,
+        &mut flags,
+        &mut ended,
+    );
+}
+
+fn choose_with_buttons(
+    mut commands: Commands,
+    mut active: ResMut<ActiveDialogue>,
+    dialogues: Res<Assets<Dialogue>>,
+    mut flags: ResMut<GameFlags>,
+    choice_buttons: Query<(&Interaction, &ui::ChoiceButton), Changed<Interaction>>,
+    mut ended: EventWriter<DialogueEnded>,
+) {
+    for (interaction, choice_button) in &choice_buttons {
+        match *interaction {
+            Interaction::Hovered => active.selected = choice_button.0,
+            Interaction::Pressed => {
+                let Some(asset) = dialogues.get(&active.dialogue) else {
+                    return;
+                };
+                let next = asset
+                    .nodes
+                    .get(&active.node)
+                    .and_then(|node| node.choices.get(active.choices[choice_button.0]))
+                    .and_then(|choice| choice.next.clone());
+                go_to(
+                    &mut commands,
+                    &mut active,
+                    asset,
+                    next,
+                    &mut flags,
+                    &mut ended,
+                );
+                return;
+            }
+            Interaction::None => {}
+        }
+    }
+}
+
+fn go_to(
+    commands: &mut Commands,
+    active: &mut ActiveDialogue,
+    asset: &Dialogue,
+    next: Option<String>,
+    flags: &mut GameFlags,
+    ended: &mut EventWriter<DialogueEnded>,
+) {
+    match next.and_then(|next| ActiveDialogue::enter(active.dialogue.clone(), asset, &next, flags))
+    {
+        Some(next) => *active = next,
+        None => end_dialogue(commands, ended),
+    }
+}
+
+fn end_dialogue(commands: &mut Commands, ended: &mut EventWriter<DialogueEnded>) {
+    commands.remove_resource::<ActiveDialogue>();
+    ended.send(DialogueEnded);
+}
+
+fn close_dialogue(
+    mut commands: Commands,
+    active: Option<Res<ActiveDialogue>>,
+    mut ended: EventWriter<DialogueEnded>,
+) {
+    if active.is_some() {
+        end_dialogue(&mut commands, &mut ended);
+    }
+}
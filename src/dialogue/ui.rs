@@ -0,0 +1,210 @@
+use crate::dialogue::{ActiveDialogue, Dialogue};
+use bevy::prelude::*;
+
+const CHARACTERS_PER_SECOND: f32 = 40.0;
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const SPEAKER_COLOR: Color = Color::rgb(0.95, 0.8, 0.4);
+const CHOICE_NORMAL: Color = Color::rgb(0.15, 0.15, 0.15);
+const CHOICE_SELECTED: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// The box at the bottom of the screen showing the current node of the conversation
+#[derive(Component)]
+pub(super) struct DialogueBox {
+    node: String,
+}
+
+#[derive(Component)]
+pub(super) struct ChoiceList;
+
+#[derive(Component)]
+pub(super) struct ChoiceButton(pub usize);
+
+/// Reveals the text of a node one character at a time
+#[derive(Component)]
+pub(super) struct Typewriter {
+    text: String,
+    length: usize,
+    shown: f32,
+}
+
+impl Typewriter {
+    fn new(text: &str) -> Self {
+        Typewriter {
+            text: text.to_owned(),
+            length: text.chars().count(),
+            shown: 0.0,
+        }
+    }
+
+    pub(super) fn is_finished(&self) -> bool {
+        self.shown as usize >= self.length
+    }
+
+    pub(super) fn finish(&mut self) {
+        self.shown = self.length as f32;
+    }
+
+    fn visible_text(&self) -> String {
+        self.text.chars().take(self.shown as usize).collect()
+    }
+}
+
+pub(super) fn refresh_dialogue_box(
+    mut commands: Commands,
+    active: Option<Res<ActiveDialogue>>,
+    dialogues: Res<Assets<Dialogue>>,
+    dialogue_box: Query<(Entity, &DialogueBox)>,
+) {
+    let shown = dialogue_box.get_single().ok();
+    let Some(active) = active else {
+        if let Some((entity, _)) = shown {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+    if shown.is_some_and(|(_, dialogue_box)| dialogue_box.node == active.node) && !active.is_added()
+    {
+        return;
+    }
+    if let Some((entity, _)) = shown {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(node) = dialogues
+        .get(&active.dialogue)
+        .and_then(|dialogue| dialogue.nodes.get(&active.node))
+    else {
+        return;
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    padding: UiRect::all(Val::Px(20.)),
+                    ..default()
+                },
+                ..default()
+            },
+            DialogueBox {
+                node: active.node.clone(),
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(80.),
+                        max_width: Val::Px(800.),
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(16.)),
+                        row_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.05, 0.05, 0.05, 0.85).into(),
+                    ..default()
+                })
+                .with_children(|panel| {
+                    if let Some(speaker) = &node.speaker {
+                        panel.spawn(TextBundle::from_section(
+                            speaker.clone(),
+                            TextStyle {
+                                font_size: 22.0,
+                                color: SPEAKER_COLOR,
+                                ..default()
+                            },
+                        ));
+                    }
+                    panel.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 20.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        ),
+                        Typewriter::new(&node.text),
+                    ));
+                    panel
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    row_gap: Val::Px(4.),
+                                    ..default()
+                                },
+                                visibility: Visibility::Hidden,
+                                ..default()
+                            },
+                            ChoiceList,
+                        ))
+                        .with_children(|choices| {
+                            for (position, &index) in active.choices.iter().enumerate() {
+                                choices
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                padding: UiRect::axes(Val::Px(10.), Val::Px(6.)),
+                                                ..default()
+                                            },
+                                            background_color: CHOICE_NORMAL.into(),
+                                            ..default()
+                                        },
+                                        ChoiceButton(position),
+                                    ))
+                                    .with_children(|button| {
+                                        button.spawn(TextBundle::from_section(
+                                            node.choices[index].text.clone(),
+                                            TextStyle {
+                                                font_size: 18.0,
+                                                color: TEXT_COLOR,
+                                                ..default()
+                                            },
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+pub(super) fn type_dialogue_text(
+    time: Res<Time>,
+    mut typewriter: Query<(&mut Typewriter, &mut Text)>,
+    mut choice_list: Query<&mut Visibility, With<ChoiceList>>,
+) {
+    for (mut typewriter, mut text) in &mut typewriter {
+        let before = typewriter.shown as usize;
+        if !typewriter.is_finished() {
+            typewriter.shown += CHARACTERS_PER_SECOND * time.delta_seconds();
+        }
+        if before != typewriter.shown as usize || text.sections[0].value.is_empty() {
+            text.sections[0].value = typewriter.visible_text();
+        }
+        if typewriter.is_finished() {
+            for mut visibility in &mut choice_list {
+                *visibility = Visibility::Inherited;
+            }
+        }
+    }
+}
+
+pub(super) fn highlight_selected_choice(
+    active: Option<Res<ActiveDialogue>>,
+    mut choice_buttons: Query<(&ChoiceButton, &mut BackgroundColor)>,
+) {
+    let Some(active) = active else {
+        return;
+    };
+    for (choice_button, mut color) in &mut choice_buttons {
+        *color = if choice_button.0 == active.selected {
+            CHOICE_SELECTED.into()
+        } else {
+            CHOICE_NORMAL.into()
+        };
+    }
+}
@@ -0,0 +1,508 @@
+use crate::boundary::ShowBoundaries;
+use crate::helpe::Bounding;
+use crate::level::{Level, LevelProp};
+use crate::loading::{DataAssets, TextureAssets};
+use crate::player::GameCamera;
+use crate::scenery::{spawn_level_props, LevelPropIndex, LevelProps, SCENERY_SCALE};
+use crate::GameState;
+use bevy::input::mouse::MouseWheel;
+use bevy::math::bounding::{Aabb2d, BoundingVolume};
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+
+/// The level file the editor saves to, relative to the directory the game is run from
+const LEVEL_PATH: &str = "assets/levels/yard.level.ron";
+/// Edge length of the grid props snap to in world units
+const GRID_SIZE: f32 = 16.0;
+/// How fast the camera pans in world units per second
+const PAN_SPEED: f32 = 800.0;
+/// How much a prop grows or shrinks per step of the mouse wheel
+const SCALE_STEP: f32 = 0.1;
+const COLLIDER_COLOR: Color = Color::rgb(0.2, 0.9, 0.3);
+const SELECTION_COLOR: Color = Color::WHITE;
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+
+pub struct EditorPlugin;
+
+/// This plugin adds the level editor, F2 switches between `GameState::Playing` and `GameState::Editor`
+/// The editor places props of the authored yard and saves them to its level file
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_editor.run_if(in_state(GameState::Playing).or_else(in_state(GameState::Editor))),
+        )
+        .add_systems(OnEnter(GameState::Editor), open_editor)
+        .add_systems(OnExit(GameState::Editor), close_editor)
+        .add_systems(
+            Update,
+            (
+                pan_camera,
+                pick_texture,
+                edit_with_mouse,
+                scale_selected,
+                edit_with_keys,
+                respawn_props,
+                show_status,
+                draw_colliders,
+            )
+                .chain()
+                .run_if(in_state(GameState::Editor)),
+        );
+    }
+}
+
+/// The props being edited with the history of changes to them
+#[derive(Resource)]
+struct Editor {
+    props: Vec<LevelProp>,
+    undo: Vec<Vec<LevelProp>>,
+    redo: Vec<Vec<LevelProp>>,
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    /// Index into `TextureAssets::props` of the texture placed next
+    texture: usize,
+    snap: bool,
+    /// The spawned props don't match `props` anymore
+    dirty: bool,
+    /// Whether the boundaries were shown before the editor opened
+    showed_boundaries: bool,
+}
+
+/// A prop being moved with the mouse
+struct Drag {
+    /// From the cursor to the prop's center
+    offset: Vec2,
+    /// The props before the drag started, for undoing it
+    before: Vec<LevelProp>,
+    moved: bool,
+}
+
+impl Editor {
+    /// Remembers the props for undoing the change about to be made to them
+    fn edit(&mut self) -> &mut Vec<LevelProp> {
+        self.undo.push(self.props.clone());
+        self.redo.clear();
+        self.dirty = true;
+        &mut self.props
+    }
+
+    fn undo(&mut self) {
+        if let Some(props) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.props, props));
+            self.selected = None;
+            self.dirty = true;
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(props) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.props, props));
+            self.selected = None;
+            self.dirty = true;
+        }
+    }
+
+    fn snapped(&self, position: Vec2) -> Vec2 {
+        if self.snap {
+            (position / GRID_SIZE).round() * GRID_SIZE
+        } else {
+            position
+        }
+    }
+}
+
+/// The text at the top of the screen telling what is selected and which keys do what
+#[derive(Component)]
+struct EditorStatus;
+
+fn toggle_editor(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F2) {
+        return;
+    }
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Editor),
+        GameState::Editor => next_state.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+fn open_editor(
+    mut commands: Commands,
+    data: Res<DataAssets>,
+    levels: Res<Assets<Level>>,
+    mut show_boundaries: ResMut<ShowBoundaries>,
+) {
+    let props = levels
+        .get(&data.yard)
+        .map(|level| level.props.clone())
+        .unwrap_or_default();
+    commands.insert_resource(Editor {
+        props,
+        undo: Vec::new(),
+        redo: Vec::new(),
+        selected: None,
+        drag: None,
+        texture: 0,
+        snap: true,
+        dirty: false,
+        showed_boundaries: show_boundaries.0,
+    });
+    show_boundaries.0 = true;
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                color: TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.),
+            left: Val::Px(12.),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.05, 0.05, 0.05, 0.85)),
+        EditorStatus,
+    ));
+}
+
+/// Keeps the edited props in the level, so they stay when playing on without saving
+fn close_editor(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    data: Res<DataAssets>,
+    mut levels: ResMut<Assets<Level>>,
+    mut show_boundaries: ResMut<ShowBoundaries>,
+    status: Query<Entity, With<EditorStatus>>,
+    mut camera: Query<&mut Transform, With<GameCamera>>,
+) {
+    if let Some(level) = levels.get_mut(&data.yard) {
+        level.props = editor.props.clone();
+    }
+    show_boundaries.0 = editor.showed_boundaries;
+    for entity in &status {
+        commands.entity(entity).despawn_recursive();
+    }
+    // The camera follows the player again
+    for mut transform in &mut camera {
+        transform.translation = Vec2::ZERO.extend(transform.translation.z);
+    }
+    commands.remove_resource::<Editor>();
+}
+
+fn pan_camera(
+    time: Res<Time>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut camera: Query<&mut Transform, With<GameCamera>>,
+) {
+    let mut direction = Vec2::ZERO;
+    if keyboard.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]) {
+        direction.y += 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]) {
+        direction.y -= 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]) {
+        direction.x -= 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]) {
+        direction.x += 1.0;
+    }
+    // Holding control is for shortcuts like saving with Ctrl+S
+    if direction == Vec2::ZERO
+        || keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    {
+        return;
+    }
+    for mut transform in &mut camera {
+        transform.translation +=
+            (direction.normalize() * PAN_SPEED * time.delta_seconds()).extend(0.0);
+    }
+}
+
+/// Tab picks the next texture to place, Shift+Tab the previous one
+fn pick_texture(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    textures: Res<TextureAssets>,
+    mut editor: ResMut<Editor>,
+) {
+    if !keyboard.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    let count = textures.props().len();
+    editor.texture = if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        (editor.texture + count - 1) % count
+    } else {
+        (editor.texture + 1) % count
+    };
+}
+
+/// The area a prop covers in world units
+fn prop_bounds(
+    prop: &LevelProp,
+    textures: &TextureAssets,
+    images: &Assets<Image>,
+) -> Option<Aabb2d> {
+    let image = images.get(textures.prop(&prop.texture)?)?;
+    let size = image.size().as_vec2() * prop.scale * SCENERY_SCALE;
+    Some(Aabb2d::new(prop.position, size / 2.0))
+}
+
+fn cursor_position(
+    windows: &Query<&Window>,
+    camera: &Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+/// Clicking a prop selects it and dragging moves it, clicking anywhere else places a new prop
+fn edit_with_mouse(
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    textures: Res<TextureAssets>,
+    images: Res<Assets<Image>>,
+    mut editor: ResMut<Editor>,
+    mut spawned: Query<(&LevelPropIndex, &mut Transform)>,
+) {
+    if mouse.just_released(MouseButton::Left) {
+        if let Some(drag) = editor.drag.take() {
+            if drag.moved {
+                editor.undo.push(drag.before);
+                editor.redo.clear();
+            }
+        }
+        return;
+    }
+    let Some(cursor) = cursor_position(&windows, &camera) else {
+        return;
+    };
+
+    if mouse.just_pressed(MouseButton::Right) {
+        editor.selected = None;
+    }
+    if mouse.just_pressed(MouseButton::Left) {
+        // The prop drawn on top is the last one containing the cursor
+        let clicked = editor.props.iter().rposition(|prop| {
+            prop_bounds(prop, &textures, &images)
+                .is_some_and(|bounds| bounds.closest_point(cursor) == cursor)
+        });
+        match clicked {
+            Some(index) => {
+                editor.selected = Some(index);
+                editor.drag = Some(Drag {
+                    offset: editor.props[index].position - cursor,
+                    before: editor.props.clone(),
+                    moved: false,
+                });
+            }
+            None => {
+                let (name, _) = textures.props()[editor.texture];
+                let position = editor.snapped(cursor);
+                editor.edit().push(LevelProp::new(name, position));
+                editor.selected = Some(editor.props.len() - 1);
+            }
+        }
+        return;
+    }
+
+    // Moving the dragged prop only moves its entity, the props are spawned again when it's dropped
+    let (Some(index), Some(drag)) = (editor.selected, &editor.drag) else {
+        return;
+    };
+    let position = editor.snapped(cursor + drag.offset);
+    if position == editor.props[index].position {
+        return;
+    }
+    editor.props[index].position = position;
+    if let Some(drag) = &mut editor.drag {
+        drag.moved = true;
+    }
+    for (prop, mut transform) in &mut spawned {
+        if prop.0 == index {
+            transform.translation = (position / SCENERY_SCALE).extend(transform.translation.z);
+        }
+    }
+}
+
+/// The mouse wheel scales the selected prop, only along x with Shift and only along y with Alt
+fn scale_selected(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    mut editor: ResMut<Editor>,
+) {
+    let steps: f32 = wheel.read().map(|event| event.y.signum()).sum();
+    let Some(index) = editor.selected else {
+        return;
+    };
+    if steps == 0.0 {
+        return;
+    }
+    let axes = if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        Vec2::X
+    } else if keyboard.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        Vec2::Y
+    } else {
+        Vec2::ONE
+    };
+    let prop = &mut editor.edit()[index];
+    prop.scale = (prop.scale + axes * steps * SCALE_STEP).max(Vec2::splat(SCALE_STEP));
+}
+
+/// Delete removes the selected prop, C toggles its collision and T its tiling
+/// G toggles snapping to the grid, Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes and Ctrl+S saves the level
+fn edit_with_keys(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    data: Res<DataAssets>,
+    mut levels: ResMut<Assets<Level>>,
+    mut editor: ResMut<Editor>,
+) {
+    let control = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if control {
+        let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if keyboard.just_pressed(KeyCode::KeyZ) && !shift {
+            editor.undo();
+        } else if keyboard.any_just_pressed([KeyCode::KeyZ, KeyCode::KeyY]) {
+            editor.redo();
+        } else if keyboard.just_pressed(KeyCode::KeyS) {
+            save_level(&editor, &data, &mut levels);
+        }
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::KeyG) {
+        editor.snap = !editor.snap;
+    }
+    let Some(index) = editor.selected else {
+        return;
+    };
+    if keyboard.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        editor.edit().remove(index);
+        editor.selected = None;
+    } else if keyboard.just_pressed(KeyCode::KeyC) {
+        let prop = &mut editor.edit()[index];
+        prop.collision = !prop.collision;
+    } else if keyboard.just_pressed(KeyCode::KeyT) {
+        let prop = &mut editor.edit()[index];
+        prop.tiled = !prop.tiled;
+    }
+}
+
+fn save_level(editor: &Editor, data: &DataAssets, levels: &mut Assets<Level>) {
+    let Some(level) = levels.get_mut(&data.yard) else {
+        error!("The yard level isn't loaded, there is nothing to save");
+        return;
+    };
+    level.props = editor.props.clone();
+    let text = match ron::ser::to_string_pretty(&*level, PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(error) => {
+            error!("Failed to write the level: {error}");
+            return;
+        }
+    };
+    let header = "// The yard in front of the house, see `src/level.rs` for the format\n";
+    match std::fs::write(LEVEL_PATH, format!("{header}{text}\n")) {
+        Ok(()) => info!("Saved the level to {LEVEL_PATH}"),
+        Err(error) => error!("Failed to save the level to {LEVEL_PATH}: {error}"),
+    }
+}
+
+/// Spawns the props again after they changed
+fn respawn_props(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    mut editor: ResMut<Editor>,
+    roots: Query<Entity, With<LevelProps>>,
+) {
+    if !editor.dirty || editor.drag.as_ref().is_some_and(|drag| drag.moved) {
+        return;
+    }
+    editor.dirty = false;
+    // Generated yards start without a root for the level's props
+    spawn_level_props(
+        &mut commands,
+        roots.get_single().ok(),
+        &textures,
+        &editor.props,
+    );
+}
+
+fn show_status(
+    textures: Res<TextureAssets>,
+    editor: Res<Editor>,
+    mut status: Query<&mut Text, With<EditorStatus>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    let (texture, _) = textures.props()[editor.texture];
+    let selected = match editor.selected.and_then(|index| editor.props.get(index)) {
+        Some(prop) => format!(
+            "Selected: {} at ({:.0}, {:.0}), scale ({:.1}, {:.1}), collision {}, tiled {}",
+            prop.texture,
+            prop.position.x,
+            prop.position.y,
+            prop.scale.x,
+            prop.scale.y,
+            on_off(prop.collision),
+            on_off(prop.tiled),
+        ),
+        None => "Nothing selected".to_owned(),
+    };
+    let text = format!(
+        "Editor - placing {texture} (Tab), snapping {} (G)\n{selected}\n\
+         Click to place or select, drag to move, wheel to scale (Shift: x, Alt: y)\n\
+         Del to delete, C collision, T tiling, Ctrl+Z/Ctrl+Y undo/redo, Ctrl+S save, F2 play",
+        on_off(editor.snap),
+    );
+    for mut status in &mut status {
+        status.sections[0].value = text.clone();
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Outlines every collider and the selected prop, walls and triggers are drawn by `ShowBoundaries`
+fn draw_colliders(
+    mut gizmos: Gizmos,
+    editor: Res<Editor>,
+    textures: Res<TextureAssets>,
+    images: Res<Assets<Image>>,
+    colliders: Query<&Bounding, With<Sprite>>,
+) {
+    for bounds in colliders.iter().flat_map(|bounding| bounding.boxes.iter()) {
+        gizmos.rect_2d(
+            bounds.center(),
+            0.0,
+            bounds.half_size() * 2.0,
+            COLLIDER_COLOR,
+        );
+    }
+    let selected = editor
+        .selected
+        .and_then(|index| editor.props.get(index))
+        .and_then(|prop| prop_bounds(prop, &textures, &images));
+    if let Some(bounds) = selected {
+        gizmos.rect_2d(
+            bounds.center(),
+            0.0,
+            bounds.half_size() * 2.0 + Vec2::splat(4.0),
+            SELECTION_COLOR,
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+/// Named story flags set by gameplay, e.g. by picking a choice in a conversation
+#[derive(Resource, Default, Clone, Debug)]
+pub struct GameFlags {
+    flags: HashSet<String>,
+}
+
+impl GameFlags {
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    pub fn set(&mut self, flag: impl Into<String>) {
+        self.flags.insert(flag.into());
+    }
+
+    pub fn clear(&mut self, flag: &str) {
+        self.flags.remove(flag);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.flags.iter().map(String::as_str)
+    }
+}
@@ -1,69 +1,159 @@
+#[cfg(feature = "dev")]
+use crate::atlas::AtlasError;
+use crate::atlas::AtlasPacker;
+use crate::audio::AmbientZone;
 use crate::helpe::Create;
-use crate::loading::TextureAssets;
-use crate::GameState;
+use crate::level::{Level, LevelAmbience, LevelTile, TileLayer};
+use crate::loading::{AudioAssets, DataAssets, TextureAssets};
+use crate::ron_asset::RonAssetPlugin;
+use crate::tilemap::{AutotileRules, Tilemap, Tileset};
+use crate::yard::YardLayout;
+use crate::{GameState, WorldSpawned};
+use bevy::math::bounding::Aabb2d;
 use bevy::prelude::*;
 
 pub struct GroundPlugin;
 
+/// The paved ground of the yard, the driveway and the road, drawn by its `Tilemap`
 #[derive(Component)]
 pub struct Ground;
 
-/// This plugin handles player related stuff like movement
-/// Player logic is only active during the State `GameState::Playing`
+/// The ground of the authored yard, the one its level file describes
+#[derive(Component)]
+pub struct LevelGround;
+
+/// The driveway tiles and the rules picking them, shared by the ground of every yard
+#[derive(Resource, Clone)]
+pub struct GroundTiles {
+    tileset: Tileset,
+    rules: Option<AutotileRules>,
+}
+
+impl GroundTiles {
+    /// The tilemap of `layer`, to be spawned at the layer's origin
+    pub fn tilemap(&self, layer: &TileLayer) -> Tilemap {
+        let mut tilemap = Tilemap::new(layer.size(), layer.tile_size, self.tileset.clone());
+        if let Some(rules) = &self.rules {
+            tilemap = tilemap.with_autotile(rules.clone());
+        }
+        for (position, tile) in layer.tiles() {
+            match tile {
+                LevelTile::Empty => {}
+                LevelTile::Terrain => tilemap.paint(position, true),
+                LevelTile::Tile(index) => tilemap.set(position, Some(index)),
+            }
+        }
+        tilemap
+    }
+
+    /// Packs the driveway tiles again after their images changed on disk
+    #[cfg(feature = "dev")]
+    pub fn repack(
+        &self,
+        atlases: &mut AtlasPacker,
+        tiles: &[Handle<Image>],
+    ) -> Result<(), AtlasError> {
+        atlases.repack(tiles, &self.tileset.image, &self.tileset.layout)
+    }
+}
+
+/// This plugin loads the level files and spawns the yard's ground and its ambient zones
+/// The grass, the driveway and the road are spawned when entering `GameState::Playing`
 impl Plugin for GroundPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), spawn_ground);
+        app.add_plugins(RonAssetPlugin::<Level>::new(&["level.ron"]))
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (spawn_ground, spawn_ambience).run_if(not(resource_exists::<WorldSpawned>)),
+            );
     }
 }
 
 fn spawn_ground(
     mut commands: Commands,
     textures: Res<TextureAssets>,
-    images: ResMut<Assets<Image>>,
+    data: Res<DataAssets>,
+    levels: Res<Assets<Level>>,
+    autotile_rules: Res<Assets<AutotileRules>>,
+    yard: Option<Res<YardLayout>>,
+    mut atlases: AtlasPacker,
 ) {
     // Grass
     commands.spawn(Create::tiled_bounded_sprite(
         textures.grass.clone(),
-        0.0, 0.0, 0.0,
-        true, true, 30.0
+        0.0,
+        0.0,
+        0.0,
+        true,
+        true,
+        30.0,
     ));
 
-    // Driveway
-    let driveway_texture = assemble_driveway_texture(&textures, images);
-    commands.spawn(Create::tiled_bounded_sprite(
-        driveway_texture.clone(),
-        300.0, -240.0, 0.1,
-        false, true, 10.0
-    ));
-
-    // Road
-    commands.spawn((
-        SpriteBundle {
-            texture: driveway_texture.clone(),
-            transform: Transform {
-                translation: Vec3::new(0.0, -816.0, 0.1),
-                rotation: Quat::IDENTITY,
-                scale: Vec3::new(20.0, 2.0, 1.0),
-            },
-            ..Default::default()
-        },
-        ImageScaleMode::Tiled {
-            tile_x: true,
-            tile_y: false,
-            stretch_value: 0.2,
-        },
-    ))
-    .insert(Ground);
+    // Driveway and road, without their tiles the yard is all grass
+    let tileset = atlases
+        .pack(&textures.driveway)
+        .and_then(|atlas| Tileset::new(atlas, &textures.driveway));
+    let tileset = match tileset {
+        Ok(tileset) => tileset,
+        Err(error) => {
+            error!("Failed to pack the driveway tiles: {error}");
+            return;
+        }
+    };
+    // A generated yard replaces the authored one
+    let generated = yard.map(|yard| yard.ground());
+    let layer = generated
+        .as_ref()
+        .or_else(|| levels.get(&data.yard).map(|level| &level.ground));
+    let tiles = GroundTiles {
+        tileset,
+        rules: autotile_rules.get(&data.asphalt_rules).cloned(),
+    };
+    if let Some(layer) = layer {
+        let mut ground = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(layer.origin.extend(0.1))),
+            tiles.tilemap(layer),
+            Ground,
+        ));
+        if generated.is_none() {
+            ground.insert(LevelGround);
+        }
+    } else {
+        warn!("The yard level isn't loaded, there is no driveway or road");
+    }
+    commands.insert_resource(tiles);
 }
 
-fn assemble_driveway_texture(textures: &Res<TextureAssets>, mut images: ResMut<Assets<Image>>) -> Handle<Image> {
-    let mut texture_atlas_builder = 
-        TextureAtlasBuilder::default().initial_size(Vec2::new(48.0 * 4.0, 48.0 * 2.0));
-    for handle in textures.driveway.iter() {
-        let id = handle.id();
-        texture_atlas_builder.add_texture(Some(id), images.get(handle).unwrap());
+fn spawn_ambience(
+    mut commands: Commands,
+    audio_assets: Res<AudioAssets>,
+    data: Res<DataAssets>,
+    levels: Res<Assets<Level>>,
+) {
+    // A generated yard keeps the authored yard's ambience, its grass and road are in the same place
+    if let Some(level) = levels.get(&data.yard) {
+        let zones: Vec<_> = ambient_zones(&audio_assets, &level.ambience, Vec2::ZERO).collect();
+        commands.spawn_batch(zones);
     }
-    let (_texture_atlas_layout, texture) = texture_atlas_builder.finish().unwrap();
-    let handle = images.add(texture);
-    handle
+}
+
+/// An `AmbientZone` for every zone of a level, moved by `offset` like the yards along the street
+pub fn ambient_zones<'a>(
+    audio_assets: &'a AudioAssets,
+    zones: &'a [LevelAmbience],
+    offset: Vec2,
+) -> impl Iterator<Item = AmbientZone> + 'a {
+    zones.iter().enumerate().filter_map(move |(index, zone)| {
+        let Some(sound) = audio_assets.ambience(&zone.sound) else {
+            warn!("Ambient zone {index} has an unknown sound '{}'", zone.sound);
+            return None;
+        };
+        Some(AmbientZone {
+            sound,
+            volume: zone.volume,
+            region: Aabb2d::new(zone.center + offset, zone.half_size),
+            fade_distance: zone.fade_distance,
+            phases: zone.phases.clone(),
+        })
+    })
 }
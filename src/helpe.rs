@@ -1,10 +1,11 @@
 use bevy::asset::Handle;
-use bevy::math::{Quat, Vec3};
+use bevy::math::bounding::{Aabb2d, BoundingVolume};
+use bevy::math::{Quat, Vec2, Vec3};
 use bevy::prelude::Component;
 use bevy::render::texture::Image;
-use bevy::math::bounding::Aabb2d;
 use bevy::sprite::{ImageScaleMode, SpriteBundle};
 use bevy::transform::components::Transform;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component)]
 pub struct Bounding {
@@ -17,6 +18,55 @@ impl Bounding {
     }
 }
 
+/// The collision box of a character from the walk sheet standing at `translation`
+pub fn character_bounds(translation: Vec3) -> Aabb2d {
+    // TODO: Figure out how to get the correct size from the sprite sheet
+    let character_dimensions = Vec2::new(32.0, 47.0);
+    Aabb2d::new(translation.truncate(), character_dimensions / 2.0).shrink(Vec2::new(10.0, 10.0))
+}
+
+/// The direction a character from the walk sheet is looking in
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Facing {
+    #[default]
+    Down,
+    Right,
+    Up,
+    Left,
+}
+
+impl Facing {
+    /// Vertical movement wins over horizontal movement, like in the walk animation
+    pub fn from_direction(direction: Vec2) -> Option<Self> {
+        if direction.y < 0. {
+            Some(Facing::Down)
+        } else if direction.y > 0. {
+            Some(Facing::Up)
+        } else if direction.x > 0. {
+            Some(Facing::Right)
+        } else if direction.x < 0. {
+            Some(Facing::Left)
+        } else {
+            None
+        }
+    }
+
+    /// Index of the first frame of the walk cycle in `sprite_walk.png`
+    pub fn first_frame(self) -> usize {
+        match self {
+            Facing::Down => 0,
+            Facing::Right => 6,
+            Facing::Up => 12,
+            Facing::Left => 18,
+        }
+    }
+
+    /// The next frame of the walk cycle after `index`
+    pub fn next_frame(self, index: usize) -> usize {
+        self.first_frame() + (index + 1) % 6
+    }
+}
+
 pub struct Create;
 
 impl Create {
@@ -72,7 +122,7 @@ impl Create {
                 tile_x,
                 tile_y,
                 stretch_value: 1.0 / stretch_factor,
-            }
+            },
         )
     }
 
@@ -86,11 +136,15 @@ impl Create {
         stretch_factor: f32,
     ) -> (SpriteBundle, ImageScaleMode, Bounding) {
         let (sprite_bundle, image_scale_mode_tiled) = Self::tiled_sprite(
-            texture, 
-            trans_x, trans_y, trans_z, 
-            tile_x, tile_y, stretch_factor
+            texture,
+            trans_x,
+            trans_y,
+            trans_z,
+            tile_x,
+            tile_y,
+            stretch_factor,
         );
-        
+
         (sprite_bundle, image_scale_mode_tiled, Bounding::new())
     }
-}
\ No newline at end of file
+}
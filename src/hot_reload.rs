@@ -0,0 +1,114 @@
+use crate::atlas::AtlasPacker;
+use crate::audio::AmbientZone;
+use crate::ground::{ambient_zones, Ground, GroundTiles, LevelGround};
+use crate::helpe::Bounding;
+use crate::level::Level;
+use crate::loading::{AudioAssets, DataAssets, TextureAssets};
+use crate::scenery::{spawn_level_props, LevelProps};
+use crate::yard::YardLayout;
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct HotReloadPlugin;
+
+/// This plugin brings changes to asset files into the running game, only in builds with the `dev` feature
+/// Images update by themselves, this refits their colliders and repacks the driveway atlas
+/// Changes to the yard's level file respawn its props, ground and ambience, the player stays where they are
+/// Changed music restarts the current track, see `audio::music`
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                refit_changed_images,
+                reload_level.run_if(in_state(GameState::Playing)),
+            )
+                .run_if(resource_exists::<TextureAssets>),
+        );
+    }
+}
+
+fn refit_changed_images(
+    mut events: EventReader<AssetEvent<Image>>,
+    textures: Res<TextureAssets>,
+    ground_tiles: Option<Res<GroundTiles>>,
+    mut atlases: AtlasPacker,
+    mut sprites: Query<(&Handle<Image>, &mut Transform), With<Bounding>>,
+) {
+    let mut repack = false;
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        repack |= textures.driveway.iter().any(|tile| tile.id() == *id);
+        // Colliders are measured from their sprite's image when the sprite moves
+        for (_, mut transform) in sprites.iter_mut().filter(|(image, _)| image.id() == *id) {
+            transform.set_changed();
+        }
+    }
+    if let (true, Some(ground_tiles)) = (repack, ground_tiles) {
+        match ground_tiles.repack(&mut atlases, &textures.driveway) {
+            Ok(()) => info!("Repacked the changed driveway tiles"),
+            Err(error) => error!("Failed to repack the driveway tiles: {error}"),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reload_level(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Level>>,
+    textures: Res<TextureAssets>,
+    audio_assets: Res<AudioAssets>,
+    data: Res<DataAssets>,
+    levels: Res<Assets<Level>>,
+    ground_tiles: Option<Res<GroundTiles>>,
+    yard: Option<Res<YardLayout>>,
+    props: Query<Entity, With<LevelProps>>,
+    ground: Query<Entity, With<LevelGround>>,
+    // Streamed yards keep theirs, they are children of the yard's scenery
+    zones: Query<Entity, (With<AmbientZone>, Without<Parent>)>,
+) {
+    // Read every event, stopping at the first would leave the rest for the next frame
+    let changed = events
+        .read()
+        .filter(|event| event.is_modified(&data.yard))
+        .count()
+        > 0;
+    if !changed {
+        return;
+    }
+    let Some(level) = levels.get(&data.yard) else {
+        return;
+    };
+    // A generated yard only shares the level file's ambience
+    for entity in &zones {
+        commands.entity(entity).despawn_recursive();
+    }
+    let zones: Vec<_> = ambient_zones(&audio_assets, &level.ambience, Vec2::ZERO).collect();
+    commands.spawn_batch(zones);
+    if yard.is_some() {
+        return;
+    }
+    info!("Respawning the yard's props and ground from its changed level file");
+    spawn_level_props(
+        &mut commands,
+        props.get_single().ok(),
+        &textures,
+        &level.props,
+    );
+
+    let Some(ground_tiles) = ground_tiles else {
+        return;
+    };
+    for entity in &ground {
+        commands.entity(entity).despawn_recursive();
+    }
+    let layer = &level.ground;
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(layer.origin.extend(0.1))),
+        ground_tiles.tilemap(layer),
+        Ground,
+        LevelGround,
+    ));
+}
@@ -0,0 +1,156 @@
+use crate::time_of_day::DayPhase;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A yard authored in `assets/levels/*.level.ron`
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone)]
+pub struct Level {
+    pub ground: TileLayer,
+    /// The yard's scenery, placed with the editor
+    #[serde(default)]
+    pub props: Vec<LevelProp>,
+    /// Regions with their own background sound, see `AmbientZone`
+    #[serde(default)]
+    pub ambience: Vec<LevelAmbience>,
+}
+
+/// A grid of tiles drawn as a `Tilemap`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TileLayer {
+    /// World position of the bottom left corner of the grid
+    pub origin: Vec2,
+    pub tile_size: f32,
+    /// One character per tile with the top row first, the way the layer looks in the yard
+    /// `.` is left empty, `#` is terrain whose tiles are picked by the autotile rules
+    /// and the digits `1` to `9` pick a tile from the tileset directly
+    pub rows: Vec<String>,
+}
+
+/// A sprite of the level, see `TextureAssets::props` for the textures it can use
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LevelProp {
+    pub texture: String,
+    /// The name its state is kept under in save games, see `Prop`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// World position of its center
+    pub position: Vec2,
+    #[serde(default = "LevelProp::default_scale")]
+    pub scale: Vec2,
+    /// Whether it blocks the way
+    #[serde(default = "LevelProp::default_collision")]
+    pub collision: bool,
+    /// Whether the texture repeats along its longer side instead of stretching
+    #[serde(default)]
+    pub tiled: bool,
+}
+
+impl LevelProp {
+    #[cfg(feature = "dev")]
+    pub fn new(texture: &str, position: Vec2) -> Self {
+        LevelProp {
+            texture: texture.to_owned(),
+            id: None,
+            position,
+            scale: Self::default_scale(),
+            collision: Self::default_collision(),
+            tiled: false,
+        }
+    }
+
+    fn default_scale() -> Vec2 {
+        Vec2::ONE
+    }
+
+    fn default_collision() -> bool {
+        true
+    }
+}
+
+/// An `AmbientZone` of the level, see `AudioAssets::ambience` for the sounds it can use
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LevelAmbience {
+    pub sound: String,
+    pub volume: f64,
+    /// World position of the region's center
+    pub center: Vec2,
+    pub half_size: Vec2,
+    pub fade_distance: f32,
+    /// The times of day the zone can be heard, all day if empty
+    #[serde(default)]
+    pub phases: Vec<DayPhase>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelTile {
+    Empty,
+    Terrain,
+    /// An index into the tileset
+    Tile(usize),
+}
+
+impl TileLayer {
+    /// Columns and rows, shorter rows are filled up with empty tiles
+    pub fn size(&self) -> UVec2 {
+        let width = self.rows.iter().map(|row| row.chars().count()).max();
+        UVec2::new(width.unwrap_or(0) as u32, self.rows.len() as u32)
+    }
+
+    /// The tile at every position, bottom row first like `Tilemap` stores them
+    pub fn tiles(&self) -> impl Iterator<Item = (UVec2, LevelTile)> + '_ {
+        let height = self.rows.len() as u32;
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(move |(row_index, row)| {
+                let y = height - 1 - row_index as u32;
+                row.chars().enumerate().map(move |(x, tile)| {
+                    let level_tile = match tile {
+                        '.' => LevelTile::Empty,
+                        '#' => LevelTile::Terrain,
+                        '1'..='9' => LevelTile::Tile(tile as usize - '1' as usize),
+                        _ => {
+                            warn!("Unknown tile '{tile}' in level, leaving it empty");
+                            LevelTile::Empty
+                        }
+                    };
+                    (UVec2::new(x as u32, y), level_tile)
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yard_level_names_the_props_saves_refer_to() {
+        let level: Level = ron::from_str(include_str!("../assets/levels/yard.level.ron")).unwrap();
+        let ids: Vec<&str> = level
+            .props
+            .iter()
+            .filter_map(|prop| prop.id.as_deref())
+            .collect();
+        assert_eq!(ids, ["house", "trees", "car_gray", "car_blue", "car_red"]);
+        // Fences come first so everything else is drawn over them
+        assert!(level.props[..4]
+            .iter()
+            .all(|prop| prop.texture.starts_with("fence_") && prop.id.is_none()));
+    }
+
+    #[test]
+    fn props_without_an_id_are_saved_without_one() {
+        let prop = LevelProp {
+            texture: "house".to_owned(),
+            id: None,
+            position: Vec2::ZERO,
+            scale: Vec2::ONE,
+            collision: true,
+            tiled: false,
+        };
+        let text = ron::to_string(&prop).unwrap();
+        assert!(!text.contains("id"));
+        assert_eq!(ron::from_str::<LevelProp>(&text).unwrap(), prop);
+    }
+}
@@ -1,27 +1,70 @@
 #![allow(clippy::type_complexity)]
 
 mod actions;
+mod atlas;
 mod audio;
+mod boundary;
+mod broad_phase;
+mod dialogue;
+#[cfg(feature = "dev")]
+mod editor;
+mod flags;
 mod ground;
+mod helpe;
+#[cfg(feature = "dev")]
+mod hot_reload;
+mod level;
 mod loading;
 mod menu;
+mod navigation;
+mod npc;
+mod particles;
 mod player;
+mod ron_asset;
+mod save;
 mod scenery;
-mod helpe;
+mod settings;
+mod streaming;
+mod tilemap;
+mod time_of_day;
+mod traffic;
+mod weather;
+mod yard;
 
 use crate::actions::ActionsPlugin;
 use crate::audio::InternalAudioPlugin;
+use crate::boundary::BoundaryPlugin;
+use crate::broad_phase::BroadPhasePlugin;
+use crate::dialogue::DialoguePlugin;
+#[cfg(feature = "dev")]
+use crate::editor::EditorPlugin;
 use crate::ground::GroundPlugin;
+#[cfg(feature = "dev")]
+use crate::hot_reload::HotReloadPlugin;
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
+use crate::navigation::NavigationPlugin;
+use crate::npc::NpcPlugin;
+use crate::particles::ParticlesPlugin;
 use crate::player::PlayerPlugin;
+use crate::save::SavePlugin;
 use crate::scenery::SceneryPlugin;
+use crate::settings::SettingsPlugin;
+use crate::streaming::StreamingPlugin;
+use crate::tilemap::TilemapPlugin;
+use crate::time_of_day::TimeOfDayPlugin;
+use crate::traffic::TrafficPlugin;
+use crate::weather::WeatherPlugin;
+use crate::yard::YardPlugin;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
 use bevy::diagnostic::LogDiagnosticsPlugin;
 use bevy::prelude::*;
 
+pub use crate::save::SaveDirectory;
+pub use crate::yard::YardSeed;
+
 // This example game uses States to separate logic
 // See https://bevy-cheatbook.github.io/programming/states.html
 // Or https://github.com/bevyengine/bevy/blob/main/examples/ecs/state.rs
@@ -30,25 +73,75 @@ enum GameState {
     // During the loading State the LoadingPlugin will load our assets
     #[default]
     Loading,
+    // Some assets failed to load, the failed files are listed with a button to try again
+    LoadingFailed,
     // During this State the actual game logic is executed
     Playing,
     // Here the menu is drawn and waiting for player interaction
     Menu,
+    // Props of the yard are placed with the mouse, only in builds with the `dev` feature
+    #[cfg(feature = "dev")]
+    Editor,
 }
 
+impl GameState {
+    /// The playlist of `assets/audio/soundtrack.playlists.ron` played in this state
+    fn playlist(&self) -> Option<&'static str> {
+        match self {
+            GameState::Loading | GameState::LoadingFailed => None,
+            GameState::Playing => Some("Playing"),
+            GameState::Menu => Some("Menu"),
+            // The yard keeps its music while it is edited
+            #[cfg(feature = "dev")]
+            GameState::Editor => Some("Playing"),
+        }
+    }
+}
+
+/// Inserted once the yard has been spawned, coming back to `GameState::Playing`
+/// from the editor doesn't spawn it again
+#[derive(Resource)]
+struct WorldSpawned;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.init_state::<GameState>().add_plugins((
-            LoadingPlugin,
-            MenuPlugin,
-            ActionsPlugin,
-            InternalAudioPlugin,
-            PlayerPlugin,
-            GroundPlugin,
-            SceneryPlugin,
-        ));
+        app.init_state::<GameState>()
+            .add_systems(OnEnter(GameState::Playing), |mut commands: Commands| {
+                commands.insert_resource(WorldSpawned)
+            })
+            .add_plugins((
+                LoadingPlugin,
+                MenuPlugin,
+                ActionsPlugin,
+                InternalAudioPlugin,
+                PlayerPlugin,
+                GroundPlugin,
+                SceneryPlugin,
+                TrafficPlugin,
+                NavigationPlugin,
+                NpcPlugin,
+                DialoguePlugin,
+                SavePlugin,
+                SettingsPlugin,
+            ))
+            // The yard's surroundings, there are too many plugins for a single tuple
+            .add_plugins((
+                TimeOfDayPlugin,
+                WeatherPlugin,
+                ParticlesPlugin,
+                TilemapPlugin,
+                YardPlugin,
+                StreamingPlugin,
+                BoundaryPlugin,
+                BroadPhasePlugin,
+            ));
+
+        #[cfg(feature = "dev")]
+        {
+            app.add_plugins((EditorPlugin, HotReloadPlugin));
+        }
 
         // #[cfg(debug_assertions)]
         {
@@ -1,21 +1,63 @@
+use crate::audio::Playlists;
+use crate::level::Level;
+use crate::npc::NpcRoster;
+use crate::tilemap::AutotileRules;
 use crate::GameState;
+use bevy::asset::{
+    LoadState, RecursiveDependencyLoadState, UntypedAssetLoadFailedEvent, UntypedHandle,
+};
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::AudioSource;
+use iyes_progress::prelude::*;
+
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const BAR_WIDTH: f32 = 400.0;
+const BAR_BACKGROUND: Color = Color::rgb(0.15, 0.15, 0.15);
+const BAR_FILL: Color = Color::rgb(0.35, 0.6, 0.3);
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::rgb(0.25, 0.25, 0.25);
 
 pub struct LoadingPlugin;
 
 /// This plugin loads all assets using [`AssetLoader`] from a third party bevy plugin
 /// Alternatively you can write the logic to load assets yourself
 /// If interested, take a look at <https://bevy-cheatbook.github.io/features/assets.html>
+/// A progress bar is shown while loading, if a file fails to load `GameState::LoadingFailed`
+/// lists the files that failed and lets the player try again
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_loading_state(
-            LoadingState::new(GameState::Loading)
-                .continue_to_state(GameState::Menu)
-                .load_collection::<AudioAssets>()
-                .load_collection::<TextureAssets>(),
-        );
+        // The progress plugin moves on to the menu once everything is loaded
+        app.add_plugins(ProgressPlugin::new(GameState::Loading).continue_to(GameState::Menu))
+            .add_loading_state(
+                LoadingState::new(GameState::Loading)
+                    .on_failure_continue_to_state(GameState::LoadingFailed)
+                    .load_collection::<AudioAssets>()
+                    .load_collection::<TextureAssets>()
+                    .load_collection::<DataAssets>(),
+            )
+            .add_systems(
+                OnEnter(GameState::Loading),
+                (remember_loading_handles, spawn_loading_screen),
+            )
+            .add_systems(
+                Update,
+                update_progress_bar
+                    .after(LoadingStateSet(GameState::Loading))
+                    .run_if(in_state(GameState::Loading)),
+            )
+            .add_systems(OnExit(GameState::Loading), despawn_screen)
+            .add_systems(OnEnter(GameState::LoadingFailed), spawn_failure_screen)
+            .add_systems(
+                Update,
+                (
+                    click_retry_button.run_if(not(resource_exists::<Retrying>)),
+                    wait_for_retry,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::LoadingFailed)),
+            )
+            .add_systems(OnExit(GameState::LoadingFailed), despawn_screen);
     }
 }
 
@@ -26,12 +68,24 @@ impl Plugin for LoadingPlugin {
 pub struct AudioAssets {
     #[asset(path = "audio/flying.ogg")]
     pub flying: Handle<AudioSource>,
-    #[asset(path = "audio/kickstart.wav")]
-    pub kickstart: Handle<AudioSource>,
-    #[asset(path = "audio/no_rain.wav")]
-    pub no_rain: Handle<AudioSource>,
+    #[asset(path = "audio/engine.wav")]
+    pub engine: Handle<AudioSource>,
     #[asset(path = "audio/galaxie.wav")]
     pub galaxie: Handle<AudioSource>,
+    #[asset(path = "audio/ambience/birds.wav")]
+    pub birds: Handle<AudioSource>,
+    #[asset(path = "audio/ambience/traffic.wav")]
+    pub traffic: Handle<AudioSource>,
+}
+
+impl AudioAssets {
+    /// The sounds a level's ambient zones can loop, by the name the level file uses
+    pub fn ambience(&self, name: &str) -> Option<Handle<AudioSource>> {
+        [("birds", &self.birds), ("traffic", &self.traffic)]
+            .into_iter()
+            .find(|(sound, _)| *sound == name)
+            .map(|(_, sound)| sound.clone())
+    }
 }
 
 #[derive(AssetCollection, Resource)]
@@ -48,9 +102,6 @@ pub struct TextureAssets {
     #[asset(path = "textures/grass.png")]
     pub grass: Handle<Image>,
 
-    #[asset(path = "textures/asphalt1.png")]
-    pub asphalt: Handle<Image>,
-
     #[asset(texture_atlas_layout(tile_size_x = 32., tile_size_y = 47., columns = 6, rows = 4))]
     pub sprite_layout: Handle<TextureAtlasLayout>,
 
@@ -58,9 +109,6 @@ pub struct TextureAssets {
     #[asset(path = "textures/sprite_walk.png")]
     pub sprite_walk: Handle<Image>,
 
-    #[asset(texture_atlas_layout(tile_size_x = 48., tile_size_y = 48., columns = 2, rows = 4))]
-    pub driveway_layout: Handle<TextureAtlasLayout>,
-
     #[asset(
         paths(
             "textures/asphalt1.png",
@@ -76,6 +124,14 @@ pub struct TextureAssets {
     )]
     pub driveway: Vec<Handle<Image>>,
 
+    /// Dust puffs in the first row, a leaf turning over in the second
+    #[asset(texture_atlas_layout(tile_size_x = 8., tile_size_y = 8., columns = 4, rows = 2))]
+    pub particles_layout: Handle<TextureAtlasLayout>,
+
+    #[asset(image(sampler = nearest))]
+    #[asset(path = "textures/particles.png")]
+    pub particles: Handle<Image>,
+
     #[asset(path = "textures/tree1.png")]
     pub tree1: Handle<Image>,
 
@@ -100,3 +156,331 @@ pub struct TextureAssets {
     #[asset(path = "textures/fence_vertical.png")]
     pub fence_vertical: Handle<Image>,
 }
+
+impl TextureAssets {
+    /// The textures level props can use, by the name they are saved with
+    pub fn props(&self) -> [(&'static str, &Handle<Image>); 8] {
+        [
+            ("tree1", &self.tree1),
+            ("house", &self.house),
+            ("car_right_blue", &self.car_right_blue),
+            ("car_right_red", &self.car_right_red),
+            ("car_right_gray", &self.car_right_gray),
+            ("fence_horizontal", &self.fence_horizontal),
+            ("fence_vertical", &self.fence_vertical),
+            ("fence_left_corner", &self.fence_left_corner),
+        ]
+    }
+
+    pub fn prop(&self, name: &str) -> Option<Handle<Image>> {
+        self.props()
+            .into_iter()
+            .find(|(prop, _)| *prop == name)
+            .map(|(_, texture)| texture.clone())
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct DataAssets {
+    #[asset(path = "npcs/neighbors.npcs.ron")]
+    pub neighbors: Handle<NpcRoster>,
+    /// Its tracks are loaded along with it
+    #[asset(path = "audio/soundtrack.playlists.ron")]
+    pub playlists: Handle<Playlists>,
+    #[asset(path = "levels/yard.level.ron")]
+    pub yard: Handle<Level>,
+    /// Picks the driveway and road tiles
+    #[asset(path = "levels/asphalt.autotile.ron")]
+    pub asphalt_rules: Handle<AutotileRules>,
+}
+
+/// The handles of everything the collections load, to find the files that failed
+#[derive(Resource)]
+struct LoadingHandles(Vec<UntypedHandle>);
+
+/// The loading or failure screen with its camera, there is no other camera yet
+#[derive(Component)]
+struct LoadingScreen;
+
+/// The part of the progress bar that fills up
+#[derive(Component)]
+struct ProgressBar;
+
+#[derive(Component)]
+struct RetryButton;
+
+/// The text on the retry button
+#[derive(Component)]
+struct RetryLabel;
+
+/// The failed files being read again, loading starts over once none of them has failed anymore
+#[derive(Resource)]
+struct Retrying(Vec<UntypedHandle>);
+
+/// The collections hand out the handles of their assets again, those are already loading
+fn remember_loading_handles(world: &mut World) {
+    let mut handles = AudioAssets::load(world);
+    handles.extend(TextureAssets::load(world));
+    handles.extend(DataAssets::load(world));
+    world.insert_resource(LoadingHandles(handles));
+}
+
+fn spawn_screen(commands: &mut Commands) -> Entity {
+    commands.spawn((Camera2dBundle::default(), LoadingScreen));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .id()
+}
+
+fn text(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            color: TEXT_COLOR,
+            ..default()
+        },
+    )
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    let screen = spawn_screen(&mut commands);
+    commands.entity(screen).with_children(|children| {
+        children.spawn(text("Loading", 40.0));
+        children
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(BAR_WIDTH),
+                    height: Val::Px(24.0),
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                background_color: BAR_BACKGROUND.into(),
+                ..default()
+            })
+            .with_children(|bar| {
+                bar.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        background_color: BAR_FILL.into(),
+                        ..default()
+                    },
+                    ProgressBar,
+                ));
+            });
+    });
+}
+
+fn update_progress_bar(
+    progress: Option<Res<ProgressCounter>>,
+    mut bar: Query<&mut Style, With<ProgressBar>>,
+) {
+    let Some(progress) = progress else {
+        return;
+    };
+    let done: f32 = progress.progress().into();
+    for mut style in &mut bar {
+        style.width = Val::Percent(done * 100.0);
+    }
+}
+
+/// The paths of the assets that failed to load, or that depend on a file that did
+fn failed_paths(asset_server: &AssetServer, handles: &[UntypedHandle]) -> Vec<String> {
+    handles
+        .iter()
+        .filter_map(|handle| {
+            let path = handle.path()?.to_string();
+            if asset_server.get_load_state(handle.id()) == Some(LoadState::Failed) {
+                Some(path)
+            } else if asset_server.get_recursive_dependency_load_state(handle.id())
+                == Some(RecursiveDependencyLoadState::Failed)
+            {
+                Some(format!("{path} (a file it uses)"))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn spawn_failure_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    handles: Res<LoadingHandles>,
+) {
+    let failed = failed_paths(&asset_server, &handles.0);
+    for path in failed.iter() {
+        error!("Failed to load {path}");
+    }
+    let screen = spawn_screen(&mut commands);
+    commands.entity(screen).with_children(|children| {
+        children.spawn(text("Some files failed to load", 40.0));
+        for path in failed {
+            children.spawn(text(path, 20.0));
+        }
+        children
+            .spawn((
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(160.0),
+                        height: Val::Px(50.0),
+                        margin: UiRect::top(Val::Px(20.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+                RetryButton,
+            ))
+            .with_children(|parent| {
+                parent.spawn((text("Retry", 30.0), RetryLabel));
+            });
+    });
+}
+
+/// Loads the failed files again, `wait_for_retry` goes back to loading once they are read
+fn click_retry_button(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    handles: Res<LoadingHandles>,
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RetryButton>),
+    >,
+    mut labels: Query<&mut Text, With<RetryLabel>>,
+) {
+    for (interaction, mut color) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => {
+                // Files that only failed through a file they use are read again along with it
+                let failed: Vec<UntypedHandle> = handles
+                    .0
+                    .iter()
+                    .filter(|handle| {
+                        asset_server.get_recursive_dependency_load_state(handle.id())
+                            == Some(RecursiveDependencyLoadState::Failed)
+                    })
+                    .cloned()
+                    .collect();
+                for path in failed.iter().filter_map(|handle| handle.path()) {
+                    asset_server.reload(path.clone());
+                }
+                for mut label in &mut labels {
+                    label.sections[0].value = "Retrying".to_owned();
+                }
+                commands.insert_resource(Retrying(failed));
+            }
+            Interaction::Hovered => *color = BUTTON_HOVERED.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+        }
+    }
+}
+
+/// Reloading is asynchronous and a reloaded file stays failed until it has been read again,
+/// going back to loading before that would fail again right away
+fn wait_for_retry(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    retrying: Option<Res<Retrying>>,
+    mut failures: EventReader<UntypedAssetLoadFailedEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut labels: Query<&mut Text, With<RetryLabel>>,
+) {
+    // Reads the failures that took the game here too, before anything is retried
+    let failed: Vec<_> = failures.read().collect();
+    let Some(retrying) = retrying else {
+        return;
+    };
+    // A file that fails again stays failed, so the player can try once more
+    for failure in failed.iter() {
+        error!("Failed to load {} again: {}", failure.path, failure.error);
+    }
+    if !failed.is_empty() {
+        for mut label in &mut labels {
+            label.sections[0].value = "Retry".to_owned();
+        }
+        commands.remove_resource::<Retrying>();
+        return;
+    }
+    let still_failed = retrying.0.iter().any(|handle| {
+        asset_server.get_recursive_dependency_load_state(handle.id())
+            == Some(RecursiveDependencyLoadState::Failed)
+    });
+    if !still_failed {
+        commands.remove_resource::<Retrying>();
+        next_state.set(GameState::Loading);
+    }
+}
+
+fn despawn_screen(mut commands: Commands, screen: Query<Entity, With<LoadingScreen>>) {
+    for entity in &screen {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yard::{YardLayout, YardPlugin};
+    use bevy::asset::AssetPlugin;
+    use std::time::Duration;
+
+    #[test]
+    fn missing_files_lead_to_the_failure_screen() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: "tests/fixtures/missing".to_owned(),
+                watch_for_changes_override: Some(false),
+                ..default()
+            },
+        ))
+        .init_asset::<Image>()
+        .init_asset::<TextureAtlasLayout>()
+        .init_asset::<AudioSource>()
+        .init_asset::<Playlists>()
+        .init_asset::<Level>()
+        .init_asset::<NpcRoster>()
+        .init_asset::<AutotileRules>()
+        .init_state::<GameState>()
+        .add_plugins((LoadingPlugin, YardPlugin));
+
+        // Leaving `GameState::Loading` without the textures must not generate a yard
+        for _ in 0..500 {
+            app.update();
+            if *app.world.resource::<State<GameState>>() == GameState::LoadingFailed {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(
+            *app.world.resource::<State<GameState>>(),
+            GameState::LoadingFailed
+        );
+        assert!(!app.world.contains_resource::<TextureAssets>());
+        assert!(!app.world.contains_resource::<YardLayout>());
+        let mut buttons = app.world.query::<&RetryButton>();
+        assert_eq!(buttons.iter(&app.world).count(), 1);
+    }
+}
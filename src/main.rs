@@ -6,7 +6,7 @@ use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
-use camp_game::GamePlugin; // ToDo: Replace camp_game with your new crate name.
+use camp_game::{GamePlugin, YardSeed}; // ToDo: Replace camp_game with your new crate name.
 use std::io::Cursor;
 use winit::window::Icon;
 
@@ -26,6 +26,12 @@ fn main() {
             }),
             ..default()
         }))
+        // `CAMP_YARD_SEED=42` plays in a generated yard instead of the authored one
+        .insert_resource(YardSeed(
+            std::env::var("CAMP_YARD_SEED")
+                .ok()
+                .and_then(|seed| seed.parse().ok()),
+        ))
         .add_plugins(GamePlugin)
         .add_systems(Startup, set_window_icon)
         .run();
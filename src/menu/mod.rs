@@ -0,0 +1,452 @@
+use crate::loading::TextureAssets;
+use crate::save::{self, SaveDirectory, SaveSlots, SLOT_COUNT};
+use crate::GameState;
+use bevy::prelude::*;
+
+mod settings;
+
+pub struct MenuPlugin;
+
+/// This plugin is responsible for the game menu, picking a save slot and the settings screen
+/// The menu is only drawn during the State `GameState::Menu` and is removed when that state is exited
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<MenuScreen>()
+            .add_systems(OnEnter(GameState::Menu), setup_menu)
+            .add_systems(
+                Update,
+                (
+                    click_play_button,
+                    click_menu_button,
+                    refresh_slot_list.run_if(resource_changed::<SaveSlots>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Menu)),
+            )
+            .add_systems(
+                OnEnter(MenuScreen::Settings),
+                (hide_main_menu, settings::spawn_settings_screen),
+            )
+            .add_systems(
+                Update,
+                (
+                    settings::click_settings_button,
+                    settings::drag_sliders,
+                    settings::capture_rebinding,
+                    settings::refresh_settings_screen,
+                )
+                    .chain()
+                    .after(click_play_button)
+                    .run_if(in_state(MenuScreen::Settings)),
+            )
+            .add_systems(
+                OnExit(MenuScreen::Settings),
+                (settings::close_settings_screen, show_main_menu),
+            )
+            .add_systems(OnExit(GameState::Menu), cleanup_menu);
+    }
+}
+
+/// Which page of the menu is shown while in `GameState::Menu`
+#[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
+enum MenuScreen {
+    #[default]
+    Main,
+    Settings,
+}
+
+#[derive(Component)]
+struct ButtonColors {
+    normal: Color,
+    hovered: Color,
+}
+
+impl Default for ButtonColors {
+    fn default() -> Self {
+        ButtonColors {
+            normal: Color::rgb(0.15, 0.15, 0.15),
+            hovered: Color::rgb(0.25, 0.25, 0.25),
+        }
+    }
+}
+
+#[derive(Component)]
+struct Menu;
+
+/// Holds the new game and continue buttons and one row per save slot,
+/// rebuilt whenever the slots change
+#[derive(Component)]
+struct SlotList;
+
+#[derive(Component, Clone, Copy)]
+enum MenuAction {
+    Continue,
+    NewGame,
+    Load(usize),
+    Delete(usize),
+    Settings,
+}
+
+fn setup_menu(mut commands: Commands, textures: Res<TextureAssets>, slots: Res<SaveSlots>) {
+    info!("menu");
+    commands.spawn(Camera2dBundle {
+        camera: Camera {
+            order: 1,
+            ..default()
+        },
+        ..default()
+    });
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            Menu,
+        ))
+        .with_children(|children| {
+            children.spawn(ImageBundle {
+                image: textures.camp.clone().into(),
+                style: Style {
+                    width: Val::Px(512.0),
+                    ..default()
+                },
+                ..default()
+            });
+            children
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            row_gap: Val::Px(6.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    SlotList,
+                ))
+                .with_children(|list| spawn_slot_buttons(list, &slots));
+            spawn_text_button(children, "Settings", 240.0, 30.0, MenuAction::Settings);
+        });
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::SpaceAround,
+                    bottom: Val::Px(5.),
+                    width: Val::Percent(100.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                ..default()
+            },
+            Menu,
+        ))
+        .with_children(|children| {
+            children
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(170.0),
+                            height: Val::Px(50.0),
+                            justify_content: JustifyContent::SpaceAround,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(5.)),
+                            ..Default::default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..Default::default()
+                    },
+                    ButtonColors {
+                        normal: Color::NONE,
+                        ..default()
+                    },
+                    OpenLink("https://bevyengine.org"),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Made with Bevy",
+                        TextStyle {
+                            font_size: 15.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    ));
+                    parent.spawn(ImageBundle {
+                        image: textures.bevy.clone().into(),
+                        style: Style {
+                            width: Val::Px(32.),
+                            ..default()
+                        },
+                        ..default()
+                    });
+                });
+            children
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(170.0),
+                            height: Val::Px(50.0),
+                            justify_content: JustifyContent::SpaceAround,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(5.)),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..Default::default()
+                    },
+                    ButtonColors {
+                        normal: Color::NONE,
+                        hovered: Color::rgb(0.25, 0.25, 0.25),
+                    },
+                    OpenLink("https://github.com/NiklasEi/camp_game_template"),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Open source",
+                        TextStyle {
+                            font_size: 15.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    ));
+                    parent.spawn(ImageBundle {
+                        image: textures.github.clone().into(),
+                        style: Style {
+                            width: Val::Px(32.),
+                            ..default()
+                        },
+                        ..default()
+                    });
+                });
+        });
+}
+
+fn spawn_slot_buttons(list: &mut ChildBuilder, slots: &SaveSlots) {
+    // A new game needs an empty slot, saves are only overwritten by deleting them first
+    let free_slot = slots.first_empty().is_some();
+    let button_colors = if free_slot {
+        ButtonColors::default()
+    } else {
+        ButtonColors {
+            normal: Color::rgb(0.1, 0.1, 0.1),
+            hovered: Color::rgb(0.1, 0.1, 0.1),
+        }
+    };
+    let mut new_game = list.spawn(ButtonBundle {
+        style: Style {
+            width: Val::Px(240.0),
+            height: Val::Px(50.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            margin: UiRect::bottom(Val::Px(4.)),
+            ..Default::default()
+        },
+        background_color: button_colors.normal.into(),
+        ..Default::default()
+    });
+    new_game.insert(button_colors).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            "New Game",
+            TextStyle {
+                font_size: 40.0,
+                color: if free_slot {
+                    Color::rgb(0.9, 0.9, 0.9)
+                } else {
+                    Color::rgb(0.4, 0.4, 0.4)
+                },
+                ..default()
+            },
+        ));
+    });
+    if free_slot {
+        new_game.insert(MenuAction::NewGame);
+    } else {
+        list.spawn(TextBundle::from_section(
+            "All slots are full, delete one to start a new game",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::rgb(0.6, 0.6, 0.6),
+                ..default()
+            },
+        ));
+    }
+    if slots.last_used.is_some() {
+        spawn_text_button(list, "Continue", 240.0, 30.0, MenuAction::Continue);
+    }
+    for slot in 0..SLOT_COUNT {
+        list.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(6.),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|row| {
+            let label = match &slots.summaries[slot] {
+                Some(summary) => format!(
+                    "Slot {}: {} min",
+                    slot + 1,
+                    (summary.play_time / 60.).floor()
+                ),
+                None => format!("Slot {}: empty", slot + 1),
+            };
+            spawn_text_button(row, &label, 200.0, 20.0, MenuAction::Load(slot));
+            if slots.summaries[slot].is_some() {
+                spawn_text_button(row, "Delete", 80.0, 20.0, MenuAction::Delete(slot));
+            }
+        });
+    }
+}
+
+fn spawn_text_button(
+    parent: &mut ChildBuilder,
+    text: &str,
+    width: f32,
+    font_size: f32,
+    action: impl Bundle,
+) {
+    let button_colors = ButtonColors::default();
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    padding: UiRect::all(Val::Px(5.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: button_colors.normal.into(),
+                ..default()
+            },
+            button_colors,
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+        });
+}
+
+#[derive(Component)]
+struct OpenLink(&'static str);
+
+fn click_play_button(
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &ButtonColors,
+            Option<&OpenLink>,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button_colors, open_link) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Some(link) = open_link {
+                    if let Err(error) = webbrowser::open(link.0) {
+                        warn!("Failed to open link {error:?}");
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+fn click_menu_button(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+    directory: Res<SaveDirectory>,
+    mut slots: ResMut<SaveSlots>,
+    interaction_query: Query<(&Interaction, &MenuAction), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *action {
+            MenuAction::Continue => {
+                if let Some(slot) = slots.last_used {
+                    if save::select_slot(&mut commands, &directory, &mut slots, slot) {
+                        next_state.set(GameState::Playing);
+                    }
+                }
+            }
+            MenuAction::NewGame => {
+                // The button is only offered while there is an empty slot
+                let Some(slot) = slots.first_empty() else {
+                    continue;
+                };
+                slots.active = slot;
+                commands.remove_resource::<save::PendingLoad>();
+                next_state.set(GameState::Playing);
+            }
+            MenuAction::Load(slot) => {
+                if save::select_slot(&mut commands, &directory, &mut slots, slot) {
+                    next_state.set(GameState::Playing);
+                }
+            }
+            MenuAction::Delete(slot) => save::clear_slot(&directory, &mut slots, slot),
+            MenuAction::Settings => next_screen.set(MenuScreen::Settings),
+        }
+        return;
+    }
+}
+
+fn refresh_slot_list(
+    mut commands: Commands,
+    slots: Res<SaveSlots>,
+    slot_list: Query<Entity, With<SlotList>>,
+) {
+    for entity in &slot_list {
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .with_children(|list| spawn_slot_buttons(list, &slots));
+    }
+}
+
+fn hide_main_menu(mut menu: Query<&mut Visibility, With<Menu>>) {
+    for mut visibility in &mut menu {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn show_main_menu(mut menu: Query<&mut Visibility, With<Menu>>) {
+    for mut visibility in &mut menu {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+fn cleanup_menu(mut commands: Commands, menu: Query<Entity, With<Menu>>) {
+    for entity in menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,314 @@
+use crate::actions::GameControl;
+use crate::menu::{spawn_text_button, MenuScreen};
+use crate::save::{self, SaveDirectory};
+use crate::settings::{Settings, WINDOW_SETTINGS};
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const UI_SCALES: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+
+#[derive(Component)]
+pub(super) struct SettingsScreen;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub(super) enum SettingsButton {
+    Fullscreen,
+    Vsync,
+    UiScale,
+    Rebind(GameControl),
+    Back,
+}
+
+/// A volume the player can drag between 0 and 100%, sits on the slider track
+#[derive(Component, Clone, Copy, PartialEq)]
+pub(super) enum Slider {
+    Master,
+    Music,
+    Sfx,
+}
+
+impl Slider {
+    fn value(&self, settings: &Settings) -> f32 {
+        match self {
+            Slider::Master => settings.master_volume,
+            Slider::Music => settings.music_volume,
+            Slider::Sfx => settings.sfx_volume,
+        }
+    }
+
+    fn value_mut<'a>(&self, settings: &'a mut Settings) -> &'a mut f32 {
+        match self {
+            Slider::Master => &mut settings.master_volume,
+            Slider::Music => &mut settings.music_volume,
+            Slider::Sfx => &mut settings.sfx_volume,
+        }
+    }
+}
+
+#[derive(Component)]
+pub(super) struct SliderFill(Slider);
+
+/// Exists while the settings screen waits for the key to bind to a control
+#[derive(Resource)]
+pub(super) struct Rebinding(GameControl);
+
+pub(super) fn spawn_settings_screen(mut commands: Commands, settings: Res<Settings>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            },
+            SettingsScreen,
+        ))
+        .with_children(|screen| {
+            for (label, slider) in [
+                ("Master volume", Slider::Master),
+                ("Music volume", Slider::Music),
+                ("Sound effects", Slider::Sfx),
+            ] {
+                spawn_row(screen, label, |row| {
+                    spawn_slider(row, slider, slider.value(&settings))
+                });
+            }
+            if WINDOW_SETTINGS {
+                spawn_row(screen, "Fullscreen", |row| {
+                    spawn_setting_button(row, SettingsButton::Fullscreen)
+                });
+                spawn_row(screen, "VSync", |row| {
+                    spawn_setting_button(row, SettingsButton::Vsync)
+                });
+            }
+            spawn_row(screen, "UI scale", |row| {
+                spawn_setting_button(row, SettingsButton::UiScale)
+            });
+            for control in GameControl::ALL {
+                spawn_row(screen, control.label(), |row| {
+                    spawn_setting_button(row, SettingsButton::Rebind(control))
+                });
+            }
+            spawn_text_button(screen, "Back", 240.0, 30.0, SettingsButton::Back);
+        });
+}
+
+fn spawn_row(
+    parent: &mut ChildBuilder,
+    label: &str,
+    spawn_control: impl FnOnce(&mut ChildBuilder),
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(420.),
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|row| {
+            row.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 20.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                },
+            ));
+            spawn_control(row);
+        });
+}
+
+fn spawn_slider(parent: &mut ChildBuilder, slider: Slider, value: f32) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(200.),
+                    height: Val::Px(16.),
+                    ..default()
+                },
+                background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                ..default()
+            },
+            RelativeCursorPosition::default(),
+            slider,
+        ))
+        .with_children(|track| {
+            track.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(value * 100.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.95, 0.8, 0.4).into(),
+                    ..default()
+                },
+                SliderFill(slider),
+            ));
+        });
+}
+
+fn spawn_setting_button(parent: &mut ChildBuilder, button: SettingsButton) {
+    // The text is filled in by `refresh_settings_screen`
+    spawn_text_button(parent, "", 200.0, 20.0, button);
+}
+
+pub(super) fn click_settings_button(
+    mut commands: Commands,
+    mut settings: ResMut<Settings>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+    rebinding: Option<Res<Rebinding>>,
+    interaction_query: Query<(&Interaction, &SettingsButton), (Changed<Interaction>, With<Button>)>,
+) {
+    // Clicks while waiting for a key shouldn't change anything else
+    if rebinding.is_some() {
+        return;
+    }
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            SettingsButton::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            SettingsButton::Vsync => settings.vsync = !settings.vsync,
+            SettingsButton::UiScale => {
+                let next = UI_SCALES
+                    .iter()
+                    .position(|scale| *scale == settings.ui_scale)
+                    .map_or(1, |index| (index + 1) % UI_SCALES.len());
+                settings.ui_scale = UI_SCALES[next];
+            }
+            SettingsButton::Rebind(control) => commands.insert_resource(Rebinding(control)),
+            SettingsButton::Back => next_screen.set(MenuScreen::Main),
+        }
+    }
+}
+
+pub(super) fn drag_sliders(
+    mut settings: ResMut<Settings>,
+    sliders: Query<(&Interaction, &RelativeCursorPosition, &Slider)>,
+) {
+    for (interaction, cursor, slider) in &sliders {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        let value = position.x.clamp(0.0, 1.0);
+        if slider.value(&settings) != value {
+            *slider.value_mut(&mut settings) = value;
+        }
+    }
+}
+
+pub(super) fn capture_rebinding(
+    mut commands: Commands,
+    rebinding: Option<Res<Rebinding>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(rebinding) = rebinding else {
+        return;
+    };
+    let Some(&key) = keyboard_input.get_just_pressed().next() else {
+        return;
+    };
+    // Escape gives up on rebinding, unless it is what the player wants to bind
+    if key != KeyCode::Escape || rebinding.0 == GameControl::Cancel {
+        settings.key_bindings.rebind(rebinding.0, key);
+    }
+    commands.remove_resource::<Rebinding>();
+}
+
+pub(super) fn refresh_settings_screen(
+    settings: Res<Settings>,
+    rebinding: Option<Res<Rebinding>>,
+    mut fills: Query<(&SliderFill, &mut Style)>,
+    buttons: Query<(&SettingsButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    // Runs every frame, so only write what differs to keep the layout from being redone
+    for (fill, mut style) in &mut fills {
+        let width = Val::Percent(fill.0.value(&settings) * 100.);
+        if style.width != width {
+            style.width = width;
+        }
+    }
+    for (button, children) in &buttons {
+        let Some(text) = setting_text(*button, &settings, rebinding.as_deref()) else {
+            continue;
+        };
+        for &child in children.iter() {
+            if let Ok(mut child_text) = texts.get_mut(child) {
+                if child_text.sections[0].value != text {
+                    child_text.sections[0].value = text.clone();
+                }
+            }
+        }
+    }
+}
+
+fn setting_text(
+    button: SettingsButton,
+    settings: &Settings,
+    rebinding: Option<&Rebinding>,
+) -> Option<String> {
+    let on_off = |on: bool| if on { "On" } else { "Off" }.to_owned();
+    match button {
+        SettingsButton::Fullscreen => Some(on_off(settings.fullscreen)),
+        SettingsButton::Vsync => Some(on_off(settings.vsync)),
+        SettingsButton::UiScale => Some(format!("{:.0}%", settings.ui_scale * 100.)),
+        SettingsButton::Rebind(control) => {
+            if rebinding.is_some_and(|rebinding| rebinding.0 == control) {
+                return Some("Press a key".to_owned());
+            }
+            let keys: Vec<String> = settings
+                .key_bindings
+                .keys(control)
+                .iter()
+                .map(|key| key_name(*key))
+                .collect();
+            Some(keys.join(" / "))
+        }
+        SettingsButton::Back => None,
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    for prefix in ["Key", "Digit", "Arrow"] {
+        if let Some(stripped) = name.strip_prefix(prefix) {
+            if !stripped.is_empty() {
+                return stripped.to_owned();
+            }
+        }
+    }
+    name
+}
+
+pub(super) fn close_settings_screen(
+    mut commands: Commands,
+    directory: Res<SaveDirectory>,
+    settings: Res<Settings>,
+    screen: Query<Entity, With<SettingsScreen>>,
+) {
+    for entity in &screen {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Rebinding>();
+    if let Err(error) = save::write_settings(&directory, &settings) {
+        error!("Failed to store the settings: {error}");
+    }
+}
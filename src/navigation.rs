@@ -0,0 +1,397 @@
+use crate::boundary::Trigger;
+use crate::helpe::Bounding;
+use crate::player::Player;
+use bevy::math::bounding::{Aabb2d, BoundingVolume};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub struct NavigationPlugin;
+
+/// Edge length of one navigation cell in world units
+pub const CELL_SIZE: f32 = 16.0;
+/// Half extent of a walking character's collision box (see `helpe::character_bounds`)
+/// Obstacles are grown by this much, so paths can be treated as paths of a single point
+pub const AGENT_HALF_SIZE: Vec2 = Vec2::new(6.0, 13.5);
+
+/// The area the grid covers around the home yard, the yard and the road in front of it
+/// Streamed yards along the street widen the grid, see `NavGrid::resize`
+pub const WORLD_MIN: Vec2 = Vec2::new(-1024.0, -960.0);
+pub const WORLD_MAX: Vec2 = Vec2::new(1024.0, 800.0);
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+/// How far around a blocked goal to look for the closest walkable cell
+const GOAL_SEARCH_RADIUS: i32 = 4;
+
+/// This plugin keeps a navigation grid in sync with the scenery colliders
+/// Only entities whose `Bounding` boxes changed are re-rasterized into the grid
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NavGrid::new(WORLD_MIN, WORLD_MAX, CELL_SIZE))
+            .add_systems(PostUpdate, bake_navigation_grid);
+    }
+}
+
+/// Grid of walkable cells baked from scenery `Bounding` boxes
+///
+/// Every cell counts the obstacles overlapping it, so adding or removing a single obstacle
+/// only touches the cells under its own boxes.
+#[derive(Resource)]
+pub struct NavGrid {
+    origin: Vec2,
+    cell_size: f32,
+    width: i32,
+    height: i32,
+    blockers: Vec<u16>,
+    /// The boxes of every obstacle, already grown by `AGENT_HALF_SIZE`
+    obstacles: HashMap<Entity, Vec<Aabb2d>>,
+}
+
+impl NavGrid {
+    pub fn new(min: Vec2, max: Vec2, cell_size: f32) -> Self {
+        let cells = ((max - min) / cell_size).ceil().as_ivec2().max(IVec2::ONE);
+        NavGrid {
+            origin: min,
+            cell_size,
+            width: cells.x,
+            height: cells.y,
+            blockers: vec![0; (cells.x * cells.y) as usize],
+            obstacles: HashMap::default(),
+        }
+    }
+
+    /// Moves the edges of the grid to `min` and `max`, e.g. as yards along the street come and go
+    /// Every obstacle is rasterized again, parts of it cut off by the old edges now count too
+    pub fn resize(&mut self, min: Vec2, max: Vec2) {
+        let obstacles = std::mem::take(&mut self.obstacles);
+        *self = NavGrid::new(min, max, self.cell_size);
+        for (obstacle, boxes) in obstacles {
+            self.update_boxes(&boxes, |count| *count = count.saturating_add(1));
+            self.obstacles.insert(obstacle, boxes);
+        }
+    }
+
+    /// Registers the boxes of `obstacle`, replacing whatever was registered for it before
+    pub fn insert(&mut self, obstacle: Entity, boxes: &[Aabb2d]) {
+        self.remove(obstacle);
+        let boxes: Vec<Aabb2d> = boxes
+            .iter()
+            .map(|bounds| bounds.grow(AGENT_HALF_SIZE))
+            .collect();
+        self.update_boxes(&boxes, |count| *count = count.saturating_add(1));
+        self.obstacles.insert(obstacle, boxes);
+    }
+
+    pub fn remove(&mut self, obstacle: Entity) {
+        let Some(boxes) = self.obstacles.remove(&obstacle) else {
+            return;
+        };
+        self.update_boxes(&boxes, |count| *count = count.saturating_sub(1));
+    }
+
+    pub fn world_to_cell(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    pub fn cell_center(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    pub fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.height
+    }
+
+    pub fn is_walkable(&self, cell: IVec2) -> bool {
+        self.in_bounds(cell) && self.blockers[self.index(cell)] == 0
+    }
+
+    pub fn is_walkable_at(&self, position: Vec2) -> bool {
+        self.is_walkable(self.world_to_cell(position))
+    }
+
+    /// Finds a walkable path from `start` to `goal`
+    ///
+    /// The returned waypoints don't include `start` and are smoothed, so consecutive waypoints
+    /// can be walked in a straight line. A blocked goal is replaced by the closest walkable cell.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let start_cell = self.world_to_cell(start);
+        if !self.in_bounds(start_cell) {
+            return None;
+        }
+        let goal_cell = self.closest_walkable(self.world_to_cell(goal))?;
+        let cells = self.a_star(start_cell, goal_cell)?;
+
+        let mut points: Vec<Vec2> = cells.iter().map(|&cell| self.cell_center(cell)).collect();
+        // Keep the exact positions at both ends when they are usable
+        points[0] = start;
+        if goal_cell == self.world_to_cell(goal) {
+            *points.last_mut()? = goal;
+        }
+        Some(self.smooth(points))
+    }
+
+    /// Whether a point can move from `from` to `to` in a straight line
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        let steps = (from.distance(to) / (self.cell_size / 4.0)).ceil().max(1.0) as usize;
+        (1..=steps).all(|step| {
+            let position = from.lerp(to, step as f32 / steps as f32);
+            self.is_walkable_at(position)
+        })
+    }
+
+    fn a_star(&self, start: IVec2, goal: IVec2) -> Option<Vec<IVec2>> {
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<IVec2, IVec2> = HashMap::default();
+        let mut cost_so_far: HashMap<IVec2, u32> = HashMap::default();
+        open.push(Reverse((heuristic(start, goal), start.x, start.y)));
+        cost_so_far.insert(start, 0);
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let current = IVec2::new(x, y);
+            if current == goal {
+                let mut path = vec![current];
+                let mut cell = current;
+                while let Some(&previous) = came_from.get(&cell) {
+                    path.push(previous);
+                    cell = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let current_cost = cost_so_far[&current];
+            for (offset, step_cost) in NEIGHBORS {
+                let next = current + offset;
+                if !self.is_walkable(next) {
+                    continue;
+                }
+                // Don't cut corners of obstacles when moving diagonally
+                if offset.x != 0
+                    && offset.y != 0
+                    && (!self.is_walkable(current + IVec2::new(offset.x, 0))
+                        || !self.is_walkable(current + IVec2::new(0, offset.y)))
+                {
+                    continue;
+                }
+                let next_cost = current_cost + step_cost;
+                if cost_so_far
+                    .get(&next)
+                    .is_none_or(|&known| next_cost < known)
+                {
+                    cost_so_far.insert(next, next_cost);
+                    came_from.insert(next, current);
+                    open.push(Reverse((next_cost + heuristic(next, goal), next.x, next.y)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Drops every waypoint that can be skipped by walking straight to a later one
+    fn smooth(&self, points: Vec<Vec2>) -> Vec<Vec2> {
+        if points.len() < 2 {
+            return points;
+        }
+        let mut smoothed = Vec::new();
+        let mut anchor = 0;
+        while anchor < points.len() - 1 {
+            let mut next = points.len() - 1;
+            while next > anchor + 1 && !self.line_of_sight(points[anchor], points[next]) {
+                next -= 1;
+            }
+            smoothed.push(points[next]);
+            anchor = next;
+        }
+        smoothed
+    }
+
+    fn closest_walkable(&self, cell: IVec2) -> Option<IVec2> {
+        if self.is_walkable(cell) {
+            return Some(cell);
+        }
+        (1..=GOAL_SEARCH_RADIUS).find_map(|radius| {
+            (-radius..=radius)
+                .flat_map(|x| (-radius..=radius).map(move |y| cell + IVec2::new(x, y)))
+                .filter(|candidate| self.is_walkable(*candidate))
+                .min_by_key(|candidate| (*candidate - cell).length_squared())
+        })
+    }
+
+    fn covered_cells(&self, bounds: &Aabb2d) -> Option<(IVec2, IVec2)> {
+        let last = IVec2::new(self.width - 1, self.height - 1);
+        let min = self.world_to_cell(bounds.min).max(IVec2::ZERO);
+        let max = self.world_to_cell(bounds.max).min(last);
+        (min.x <= max.x && min.y <= max.y).then_some((min, max))
+    }
+
+    /// Updates the cells under `boxes` that are inside the grid
+    fn update_boxes(&mut self, boxes: &[Aabb2d], mut update: impl FnMut(&mut u16)) {
+        for bounds in boxes {
+            if let Some((min, max)) = self.covered_cells(bounds) {
+                self.update_cells(min, max, &mut update);
+            }
+        }
+    }
+
+    fn update_cells(&mut self, min: IVec2, max: IVec2, mut update: impl FnMut(&mut u16)) {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let index = self.index(IVec2::new(x, y));
+                update(&mut self.blockers[index]);
+            }
+        }
+    }
+
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.width + cell.x) as usize
+    }
+}
+
+const NEIGHBORS: [(IVec2, u32); 8] = [
+    (IVec2::new(1, 0), STRAIGHT_COST),
+    (IVec2::new(-1, 0), STRAIGHT_COST),
+    (IVec2::new(0, 1), STRAIGHT_COST),
+    (IVec2::new(0, -1), STRAIGHT_COST),
+    (IVec2::new(1, 1), DIAGONAL_COST),
+    (IVec2::new(1, -1), DIAGONAL_COST),
+    (IVec2::new(-1, 1), DIAGONAL_COST),
+    (IVec2::new(-1, -1), DIAGONAL_COST),
+];
+
+/// Octile distance, admissible for 8-connected movement
+fn heuristic(from: IVec2, to: IVec2) -> u32 {
+    let delta = (to - from).abs();
+    let (long, short) = (delta.max_element() as u32, delta.min_element() as u32);
+    STRAIGHT_COST * (long - short) + DIAGONAL_COST * short
+}
+
+fn bake_navigation_grid(
+    mut grid: ResMut<NavGrid>,
+    changed: Query<
+        (Entity, &Bounding),
+        (
+            Changed<Bounding>,
+            With<Parent>,
+            Without<Player>,
+            Without<Trigger>,
+        ),
+    >,
+    mut removed: RemovedComponents<Bounding>,
+) {
+    for entity in removed.read() {
+        grid.remove(entity);
+    }
+    for (entity, bounding) in &changed {
+        grid.insert(entity, &bounding.boxes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty 320 by 320 yard centered on the origin
+    fn open_yard() -> NavGrid {
+        NavGrid::new(Vec2::splat(-160.0), Vec2::splat(160.0), CELL_SIZE)
+    }
+
+    /// A wall from the bottom to the top of the yard, splitting it into a left and a right half
+    fn splitting_wall() -> Aabb2d {
+        Aabb2d::new(Vec2::ZERO, Vec2::new(8.0, 200.0))
+    }
+
+    /// Whether every leg of the path, starting at `start`, can be walked in a straight line
+    fn walkable_legs(grid: &NavGrid, start: Vec2, path: &[Vec2]) -> bool {
+        std::iter::once(start)
+            .chain(path.iter().copied())
+            .zip(path.iter().copied())
+            .all(|(from, to)| grid.line_of_sight(from, to))
+    }
+
+    #[test]
+    fn walks_around_an_obstacle() {
+        let mut grid = open_yard();
+        let wall = Aabb2d::new(Vec2::ZERO, Vec2::new(8.0, 100.0));
+        grid.insert(Entity::from_raw(1), &[wall]);
+        let (start, goal) = (Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0));
+
+        let path = grid
+            .find_path(start, goal)
+            .expect("there is a way around the wall");
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.len() > 1, "the wall is in the way of a straight line");
+        assert!(walkable_legs(&grid, start, &path));
+    }
+
+    #[test]
+    fn finds_no_path_to_a_walled_off_goal() {
+        let mut grid = open_yard();
+        grid.insert(Entity::from_raw(1), &[splitting_wall()]);
+
+        let path = grid.find_path(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0));
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn smooths_an_open_path_into_a_straight_line() {
+        let grid = open_yard();
+        let goal = Vec2::new(100.0, 100.0);
+
+        let path = grid.find_path(Vec2::new(-100.0, -100.0), goal);
+        assert_eq!(path, Some(vec![goal]));
+    }
+
+    #[test]
+    fn rebakes_only_the_removed_obstacle() {
+        let mut grid = open_yard();
+        let (start, goal) = (Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0));
+        // Two walls on the same cells, removing one of them keeps the cells blocked
+        grid.insert(Entity::from_raw(1), &[splitting_wall()]);
+        grid.insert(Entity::from_raw(2), &[splitting_wall()]);
+
+        grid.remove(Entity::from_raw(1));
+        assert!(!grid.is_walkable_at(Vec2::ZERO));
+        assert_eq!(grid.find_path(start, goal), None);
+
+        grid.remove(Entity::from_raw(2));
+        assert!(grid.is_walkable_at(Vec2::ZERO));
+        assert_eq!(grid.find_path(start, goal), Some(vec![goal]));
+    }
+
+    #[test]
+    fn resizing_keeps_obstacles_and_adds_their_cut_off_parts() {
+        let mut grid = open_yard();
+        // Past the right edge of the grid at first
+        let wall = Aabb2d::new(Vec2::new(200.0, 0.0), Vec2::new(8.0, 200.0));
+        grid.insert(Entity::from_raw(1), &[splitting_wall()]);
+        grid.insert(Entity::from_raw(2), &[wall]);
+
+        grid.resize(Vec2::new(-160.0, -400.0), Vec2::new(480.0, 160.0));
+        assert!(!grid.is_walkable_at(Vec2::ZERO));
+        assert!(!grid.is_walkable_at(Vec2::new(200.0, -150.0)));
+        // Around the bottom of the wall, which is below the old grid
+        let (start, goal) = (Vec2::new(100.0, 0.0), Vec2::new(300.0, 0.0));
+        let path = grid
+            .find_path(start, goal)
+            .expect("the grid now reaches below the wall");
+        assert_eq!(path.last(), Some(&goal));
+        assert!(walkable_legs(&grid, start, &path));
+
+        grid.remove(Entity::from_raw(2));
+        assert!(grid.is_walkable_at(Vec2::new(200.0, -150.0)));
+    }
+
+    #[test]
+    fn moving_an_obstacle_replaces_its_old_cells() {
+        let mut grid = open_yard();
+        grid.insert(Entity::from_raw(1), &[splitting_wall()]);
+        let moved = Aabb2d::new(Vec2::new(120.0, 0.0), Vec2::new(8.0, 200.0));
+        grid.insert(Entity::from_raw(1), &[moved]);
+
+        assert!(grid.is_walkable_at(Vec2::ZERO));
+        assert!(!grid.is_walkable_at(Vec2::new(120.0, 0.0)));
+    }
+}
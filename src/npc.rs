@@ -0,0 +1,353 @@
+use crate::actions::Actions;
+use crate::boundary::Trigger;
+use crate::broad_phase::BroadPhase;
+use crate::dialogue::{ActiveDialogue, Dialogue, StartDialogue};
+use crate::helpe::{character_bounds, Bounding, Facing};
+use crate::loading::{DataAssets, TextureAssets};
+use crate::navigation::NavGrid;
+use crate::player::{GameCamera, Player};
+use crate::ron_asset::RonAssetPlugin;
+use crate::{GameState, WorldSpawned};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub struct NpcPlugin;
+
+/// Distance at which neighbors stop what they are doing and look at the player
+const NOTICE_DISTANCE: f32 = 80.0;
+/// How close a neighbor has to get to a waypoint for it to count as reached
+const ARRIVAL_DISTANCE: f32 = 2.0;
+/// Neighbors that can't get around an obstacle for this long give up on their current waypoint
+const GIVE_UP_SECONDS: f32 = 1.5;
+/// The player has to be this close to a neighbor to start a conversation
+const TALK_DISTANCE: f32 = 60.0;
+/// Taps closer than this to a neighbor count as tapping on them
+const TAP_RADIUS: f32 = 24.0;
+
+/// This plugin spawns the neighbors and lets them patrol along the routes in `npcs/neighbors.npcs.ron`
+/// NPC logic is only active during the State `GameState::Playing`
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<NpcRoster>::new(&["npcs.ron"]))
+            .add_systems(
+                OnEnter(GameState::Playing),
+                spawn_npcs.run_if(not(resource_exists::<WorldSpawned>)),
+            )
+            .add_systems(
+                Update,
+                (patrol, face_player, animate_npcs)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                talk_to_neighbors
+                    .after(crate::actions::set_button_actions)
+                    .before(crate::actions::set_move_target)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(resource_exists::<ActiveDialogue>)),
+            );
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct NpcRoster {
+    pub neighbors: Vec<NpcDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NpcDefinition {
+    pub name: String,
+    pub speed: f32,
+    pub waypoints: Vec<Waypoint>,
+    /// Path of the conversation started when talking to this neighbor
+    #[serde(default)]
+    pub dialogue: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct Waypoint {
+    pub position: Vec2,
+    /// Seconds to wait once the waypoint is reached
+    #[serde(default)]
+    pub pause: f32,
+}
+
+#[derive(Component)]
+pub struct Npc;
+
+/// Neighbors with this component can be talked to
+#[derive(Component)]
+pub struct Talker {
+    pub dialogue: Handle<Dialogue>,
+}
+
+#[derive(Component)]
+pub struct Patrol {
+    waypoints: Vec<Waypoint>,
+    current: usize,
+    speed: f32,
+    pause: Timer,
+    stuck: Timer,
+    heading: Option<Vec2>,
+    /// Path to the current waypoint, computed on the navigation grid
+    route: Vec<Vec2>,
+}
+
+impl Patrol {
+    pub fn new(definition: &NpcDefinition) -> Self {
+        Patrol {
+            waypoints: definition.waypoints.clone(),
+            current: 0,
+            speed: definition.speed,
+            pause: Timer::from_seconds(0.0, TimerMode::Once),
+            stuck: Timer::from_seconds(GIVE_UP_SECONDS, TimerMode::Once),
+            heading: None,
+            route: Vec::new(),
+        }
+    }
+
+    pub fn current_waypoint(&self) -> usize {
+        self.current
+    }
+
+    /// Continues the patrol towards the waypoint at `index`, e.g. after loading a save game
+    pub fn resume_at(&mut self, index: usize) {
+        self.current = index % self.waypoints.len();
+        self.pause = Timer::from_seconds(0.0, TimerMode::Once);
+        self.stuck.reset();
+        self.route.clear();
+    }
+
+    fn advance(&mut self) {
+        let reached = self.waypoints[self.current];
+        self.pause = Timer::from_seconds(reached.pause, TimerMode::Once);
+        self.stuck.reset();
+        self.route.clear();
+        self.current = (self.current + 1) % self.waypoints.len();
+    }
+}
+
+#[derive(Component)]
+struct NpcAnimationTimer(Timer);
+
+fn spawn_npcs(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    textures: Res<TextureAssets>,
+    data: Res<DataAssets>,
+    rosters: Res<Assets<NpcRoster>>,
+) {
+    let Some(roster) = rosters.get(&data.neighbors) else {
+        warn!("Neighbor roster is not loaded, nobody is home");
+        return;
+    };
+    for definition in roster.neighbors.iter() {
+        let Some(start) = definition.waypoints.first() else {
+            warn!("Neighbor {} has no waypoints", definition.name);
+            continue;
+        };
+        let mut npc = commands.spawn((
+            SpriteBundle {
+                texture: textures.sprite_walk.clone(),
+                transform: Transform::from_translation(start.position.extend(1.9)),
+                ..Default::default()
+            },
+            TextureAtlas::from(textures.sprite_layout.clone()),
+            NpcAnimationTimer(Timer::from_seconds(0.15, TimerMode::Repeating)),
+            Facing::default(),
+            Name::new(definition.name.clone()),
+            Npc,
+            Patrol::new(definition),
+        ));
+        if let Some(dialogue) = &definition.dialogue {
+            npc.insert(Talker {
+                dialogue: asset_server.load(dialogue.clone()),
+            });
+        }
+    }
+}
+
+fn patrol(
+    time: Res<Time>,
+    player: Query<&Transform, (With<Player>, Without<Npc>)>,
+    mut npcs: Query<(&mut Transform, &mut Patrol), With<Npc>>,
+    obstacles: Query<
+        &Bounding,
+        (
+            With<Parent>,
+            Without<Player>,
+            Without<Npc>,
+            Without<Trigger>,
+        ),
+    >,
+    broad_phase: Res<BroadPhase>,
+    nav_grid: Res<NavGrid>,
+) {
+    let player_position = player
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+    for (mut transform, mut patrol) in &mut npcs {
+        patrol.heading = None;
+        let position = transform.translation.truncate();
+        if player_position.is_some_and(|player| player.distance(position) < NOTICE_DISTANCE) {
+            continue;
+        }
+        if !patrol.pause.tick(time.delta()).finished() {
+            continue;
+        }
+        if patrol.route.is_empty() {
+            let waypoint = patrol.waypoints[patrol.current].position;
+            patrol.route = nav_grid
+                .find_path(position, waypoint)
+                .unwrap_or_else(|| vec![waypoint]);
+            patrol.route.reverse();
+        }
+        let Some(&target) = patrol.route.last() else {
+            patrol.advance();
+            continue;
+        };
+        let to_target = target - position;
+        if to_target.length() < ARRIVAL_DISTANCE {
+            patrol.route.pop();
+            if patrol.route.is_empty() {
+                patrol.advance();
+            }
+            continue;
+        }
+        let step =
+            to_target.normalize() * (patrol.speed * time.delta_seconds()).min(to_target.length());
+        // Try the direct step first and slide along obstacles the grid didn't know about yet
+        let moved = [step, Vec2::new(step.x, 0.0), Vec2::new(0.0, step.y)]
+            .into_iter()
+            .filter(|candidate| *candidate != Vec2::ZERO)
+            .find(|candidate| {
+                let bounds = character_bounds(transform.translation + candidate.extend(0.0));
+                !broad_phase.collides(&bounds, &obstacles)
+            });
+        match moved {
+            Some(movement) => {
+                transform.translation += movement.extend(0.0);
+                patrol.heading = Some(movement);
+                patrol.stuck.reset();
+            }
+            None => {
+                if patrol.stuck.tick(time.delta()).finished() {
+                    patrol.advance();
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn talk_to_neighbors(
+    actions: Res<Actions>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touch_input: Res<Touches>,
+    windows: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    player: Query<&Transform, (With<Player>, Without<Npc>)>,
+    talkers: Query<(&Transform, &Talker), With<Npc>>,
+    mut start_dialogue: EventWriter<StartDialogue>,
+) {
+    let Ok(player_transform) = player.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
+    let in_reach = talkers.iter().filter(|(transform, _)| {
+        transform.translation.truncate().distance(player_position) < TALK_DISTANCE
+    });
+
+    let talker = if actions.interact {
+        in_reach
+            .min_by(|(a, _), (b, _)| {
+                let a = a.translation.truncate().distance(player_position);
+                let b = b.translation.truncate().distance(player_position);
+                a.total_cmp(&b)
+            })
+            .map(|(_, talker)| talker)
+    } else {
+        let screen_position = if mouse_input.just_pressed(MouseButton::Left) {
+            windows.get_single().ok().and_then(Window::cursor_position)
+        } else {
+            touch_input
+                .iter_just_pressed()
+                .next()
+                .map(|touch| touch.position())
+        };
+        let tapped = screen_position.and_then(|screen_position| {
+            let (camera, camera_transform) = camera.get_single().ok()?;
+            camera.viewport_to_world_2d(camera_transform, screen_position)
+        });
+        tapped.and_then(|tapped| {
+            in_reach
+                .filter(|(transform, _)| {
+                    transform.translation.truncate().distance(tapped) < TAP_RADIUS
+                })
+                .map(|(_, talker)| talker)
+                .next()
+        })
+    };
+    if let Some(talker) = talker {
+        start_dialogue.send(StartDialogue {
+            dialogue: talker.dialogue.clone(),
+        });
+    }
+}
+
+fn face_player(
+    player: Query<&Transform, (With<Player>, Without<Npc>)>,
+    mut npcs: Query<(&Transform, &mut Facing, &mut TextureAtlas), With<Npc>>,
+) {
+    let Ok(player_transform) = player.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
+    for (transform, mut facing, mut sprite) in &mut npcs {
+        let to_player = player_position - transform.translation.truncate();
+        if to_player.length() >= NOTICE_DISTANCE {
+            continue;
+        }
+        if let Some(towards_player) = Facing::from_direction(dominant_axis(to_player)) {
+            if *facing != towards_player {
+                *facing = towards_player;
+                sprite.index = facing.first_frame();
+            }
+        }
+    }
+}
+
+fn animate_npcs(
+    time: Res<Time>,
+    mut npcs: Query<
+        (
+            &mut NpcAnimationTimer,
+            &mut TextureAtlas,
+            &mut Facing,
+            &Patrol,
+        ),
+        With<Npc>,
+    >,
+) {
+    for (mut timer, mut sprite, mut facing, patrol) in &mut npcs {
+        timer.0.tick(time.delta());
+        if !timer.0.finished() {
+            continue;
+        }
+        if let Some(heading) = patrol.heading {
+            *facing = Facing::from_direction(dominant_axis(heading)).unwrap_or(*facing);
+            sprite.index = facing.next_frame(sprite.index);
+        }
+    }
+}
+
+/// Neighbors look along the dominant axis, so someone walking mostly sideways doesn't face up or down
+fn dominant_axis(direction: Vec2) -> Vec2 {
+    if direction.x.abs() > direction.y.abs() {
+        Vec2::new(direction.x, 0.0)
+    } else {
+        Vec2::new(0.0, direction.y)
+    }
+}
@@ -0,0 +1,279 @@
+use crate::time_of_day::{tint_scene, BaseColor};
+use crate::GameState;
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::sync::Arc;
+
+pub struct ParticlesPlugin;
+
+/// This plugin spawns and moves the sprite particles of every `ParticleEmitter`
+/// Particles live in the world on their own, so they stay behind when their emitter moves on
+/// Leaving `GameState::Playing` clears them, they would hang still in the air otherwise
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (emit_particles, update_particles)
+                .chain()
+                .before(tint_scene)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(OnExit(GameState::Playing), clear_particles);
+    }
+}
+
+/// Keeps spawning particles around its entity for as long as its rate is above 0
+/// Ranges are given as (min, max) and every particle picks its own values from them
+#[derive(Component, Clone)]
+pub struct ParticleEmitter {
+    /// Particles per second
+    pub rate: f32,
+    /// Half the size of the area around the emitter that particles appear in, in world units
+    pub spawn_area: Vec2,
+    /// Added to the emitter's z, so particles can be drawn in front of or behind their source
+    pub z_offset: f32,
+    /// Seconds a particle lives
+    pub lifetime: (f32, f32),
+    pub velocity: (Vec2, Vec2),
+    /// Added to the velocity every second
+    pub gravity: Vec2,
+    /// The angle a particle starts at, in radians
+    pub rotation: (f32, f32),
+    /// Radians per second a particle turns
+    pub spin: (f32, f32),
+    pub size: Vec2,
+    /// The color over the life of a particle, multiplied with `alpha`
+    pub color: Curve<Color>,
+    pub alpha: Curve<f32>,
+    /// Drawn as a plain rectangle without one
+    pub texture: Option<ParticleTexture>,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        ParticleEmitter {
+            rate: 0.0,
+            spawn_area: Vec2::ZERO,
+            z_offset: 0.0,
+            lifetime: (1.0, 1.0),
+            velocity: (Vec2::ZERO, Vec2::ZERO),
+            gravity: Vec2::ZERO,
+            rotation: (0.0, 0.0),
+            spin: (0.0, 0.0),
+            size: Vec2::splat(4.0),
+            color: Curve::constant(Color::WHITE),
+            alpha: Curve::constant(1.0),
+            texture: None,
+        }
+    }
+}
+
+/// Frames of a texture atlas shown one after the other over the life of a particle
+#[derive(Clone)]
+pub struct ParticleTexture {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    frames: Arc<[usize]>,
+}
+
+impl ParticleTexture {
+    /// Panics without any frames
+    pub fn new(image: Handle<Image>, layout: Handle<TextureAtlasLayout>, frames: &[usize]) -> Self {
+        assert!(!frames.is_empty(), "A particle texture needs a frame");
+        ParticleTexture {
+            image,
+            layout,
+            frames: frames.into(),
+        }
+    }
+
+    fn frame(&self, life: f32) -> usize {
+        let index = (life * self.frames.len() as f32) as usize;
+        self.frames[index.min(self.frames.len() - 1)]
+    }
+}
+
+/// Values over the life of a particle, from 0 when it spawns to 1 when it is gone
+/// Keys are (life, value) in order of life, values between keys are blended linearly
+#[derive(Clone)]
+pub struct Curve<T>(Arc<[(f32, T)]>);
+
+impl<T: Blend> Curve<T> {
+    /// Panics without any keys
+    pub fn new(keys: &[(f32, T)]) -> Self {
+        assert!(!keys.is_empty(), "A curve needs a key");
+        Curve(keys.into())
+    }
+
+    pub fn constant(value: T) -> Self {
+        Curve(Arc::new([(0.0, value)]))
+    }
+
+    fn sample(&self, life: f32) -> T {
+        let keys = &self.0;
+        let Some(next) = keys.iter().position(|(at, _)| *at > life) else {
+            return keys[keys.len() - 1].1;
+        };
+        if next == 0 {
+            return keys[0].1;
+        }
+        let (from_at, from) = keys[next - 1];
+        let (to_at, to) = keys[next];
+        from.blend(to, (life - from_at) / (to_at - from_at))
+    }
+}
+
+pub trait Blend: Copy {
+    fn blend(self, other: Self, t: f32) -> Self;
+}
+
+impl Blend for f32 {
+    fn blend(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Blend for Color {
+    fn blend(self, other: Color, t: f32) -> Color {
+        Color::rgba_from_array(self.rgba_to_vec4().lerp(other.rgba_to_vec4(), t))
+    }
+}
+
+/// Particles an emitter owes from previous frames, so low rates still spawn some
+#[derive(Component, Default)]
+struct PendingParticles(f32);
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    gravity: Vec2,
+    spin: f32,
+    age: f32,
+    lifetime: f32,
+    color: Curve<Color>,
+    alpha: Curve<f32>,
+    texture: Option<ParticleTexture>,
+}
+
+impl Particle {
+    fn life(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+
+    fn color(&self) -> Color {
+        let life = self.life();
+        let color = self.color.sample(life);
+        color.with_a(color.a() * self.alpha.sample(life))
+    }
+}
+
+/// Picks a value between min and max, which may be the same
+fn between<T: Blend>(rng: &mut impl Rng, (min, max): (T, T)) -> T {
+    min.blend(max, rng.gen())
+}
+
+fn emit_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut emitters: Query<(
+        Entity,
+        &ParticleEmitter,
+        &GlobalTransform,
+        Option<&mut PendingParticles>,
+    )>,
+) {
+    let mut rng = thread_rng();
+    for (entity, emitter, transform, pending) in &mut emitters {
+        let Some(mut pending) = pending else {
+            commands.entity(entity).insert(PendingParticles::default());
+            continue;
+        };
+        if emitter.rate <= 0.0 {
+            pending.0 = 0.0;
+            continue;
+        }
+        pending.0 += emitter.rate * time.delta_seconds();
+        let origin = transform.translation() + Vec3::Z * emitter.z_offset;
+        while pending.0 >= 1.0 {
+            pending.0 -= 1.0;
+            let offset = emitter.spawn_area
+                * Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0));
+            let velocity = Vec2::new(
+                between(&mut rng, (emitter.velocity.0.x, emitter.velocity.1.x)),
+                between(&mut rng, (emitter.velocity.0.y, emitter.velocity.1.y)),
+            );
+            let particle = Particle {
+                velocity,
+                gravity: emitter.gravity,
+                spin: between(&mut rng, emitter.spin),
+                age: 0.0,
+                lifetime: between(&mut rng, emitter.lifetime).max(f32::EPSILON),
+                color: emitter.color.clone(),
+                alpha: emitter.alpha.clone(),
+                texture: emitter.texture.clone(),
+            };
+            let color = particle.color();
+            let mut entity = commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(emitter.size),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(origin + offset.extend(0.0))
+                        .with_rotation(Quat::from_rotation_z(between(&mut rng, emitter.rotation))),
+                    ..default()
+                },
+                BaseColor(color),
+            ));
+            if let Some(texture) = &particle.texture {
+                entity.insert((
+                    texture.image.clone(),
+                    TextureAtlas {
+                        layout: texture.layout.clone(),
+                        index: texture.frame(0.0),
+                    },
+                ));
+            }
+            entity.insert(particle);
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut BaseColor,
+        Option<&mut TextureAtlas>,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut base_color, atlas) in &mut particles {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let gravity = particle.gravity;
+        particle.velocity += gravity * delta;
+        transform.translation += (particle.velocity * delta).extend(0.0);
+        transform.rotate_z(particle.spin * delta);
+        base_color.0 = particle.color();
+        if let (Some(mut atlas), Some(texture)) = (atlas, &particle.texture) {
+            let index = texture.frame(particle.life());
+            if atlas.index != index {
+                atlas.index = index;
+            }
+        }
+    }
+}
+
+fn clear_particles(mut commands: Commands, particles: Query<Entity, With<Particle>>) {
+    for entity in &particles {
+        commands.entity(entity).despawn();
+    }
+}
@@ -1,47 +1,116 @@
 use crate::actions::Actions;
+use crate::audio::AudioListener;
+use crate::boundary::Trigger;
+use crate::broad_phase::BroadPhase;
+use crate::ground::Ground;
+use crate::helpe::{character_bounds, Bounding, Facing};
 use crate::loading::TextureAssets;
-use crate::helpe::Bounding;
-use crate::GameState;
-use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
+use crate::particles::{Curve, ParticleEmitter, ParticleTexture};
+use crate::tilemap::Tilemap;
+use crate::yard::YardLayout;
+use crate::{GameState, WorldSpawned};
 use bevy::prelude::*;
 
 pub struct PlayerPlugin;
 
+const KNOCKBACK_DAMPING: f32 = 6.0;
+/// Dust puffs per second while walking on the driveway or the road
+const DUST_RATE: f32 = 12.0;
+
 #[derive(Component)]
 pub struct Player;
 
+/// The camera following the player around the world
+#[derive(Component)]
+pub struct GameCamera;
+
+/// Kicks up dust at the player's feet
+#[derive(Component)]
+struct DustEmitter;
+
+/// Pushes the player away after being hit, e.g. by a car on the road
+/// The velocity decays over time and the component is removed once it has died down
+#[derive(Component)]
+pub struct Knockback {
+    pub velocity: Vec2,
+}
+
 /// This plugin handles player related stuff like movement
 /// Player logic is only active during the State `GameState::Playing`
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), spawn_player)
-            .add_systems(Update, move_player.run_if(in_state(GameState::Playing)))
-            .add_systems(
-                Update,
-                animate_sprite_system.run_if(in_state(GameState::Playing)),
-            );
+        app.add_systems(
+            OnEnter(GameState::Playing),
+            spawn_player.run_if(not(resource_exists::<WorldSpawned>)),
+        )
+        .add_systems(Update, move_player.run_if(in_state(GameState::Playing)))
+        .add_systems(
+            Update,
+            animate_sprite_system.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            apply_knockback
+                .after(move_player)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            kick_up_dust
+                .after(apply_knockback)
+                .run_if(in_state(GameState::Playing)),
+        );
     }
 }
 
-fn spawn_player(mut commands: Commands, textures: Res<TextureAssets>) {
+fn spawn_player(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    yard: Option<Res<YardLayout>>,
+) {
+    let start = yard.map_or(Vec2::new(250.0, -600.0), |yard| yard.start);
     commands
         .spawn((
             SpriteBundle {
                 texture: textures.sprite_walk.clone(),
-                transform: Transform::from_translation(Vec3::new(250.0, -600.0, 2.0)),
+                transform: Transform::from_translation(start.extend(2.0)),
                 ..Default::default()
             },
             TextureAtlas::from(textures.sprite_layout.clone()),
             AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+            Facing::default(),
         ))
         .with_children(|parent| {
-            parent.spawn(Camera2dBundle {
-                camera: Camera {
-                    order: 2,
+            parent.spawn((
+                Camera2dBundle {
+                    camera: Camera {
+                        order: 2,
+                        ..default()
+                    },
+                    ..default()
+                },
+                GameCamera,
+                AudioListener,
+            ));
+            parent.spawn((
+                SpatialBundle::from_transform(Transform::from_xyz(0.0, -20.0, -0.5)),
+                ParticleEmitter {
+                    spawn_area: Vec2::new(8.0, 2.0),
+                    lifetime: (0.3, 0.5),
+                    velocity: (Vec2::new(-20.0, 5.0), Vec2::new(20.0, 25.0)),
+                    gravity: Vec2::new(0.0, -20.0),
+                    size: Vec2::splat(8.0),
+                    color: Curve::constant(Color::rgb(0.75, 0.68, 0.55)),
+                    alpha: Curve::new(&[(0.0, 0.8), (1.0, 0.0)]),
+                    texture: Some(ParticleTexture::new(
+                        textures.particles.clone(),
+                        textures.particles_layout.clone(),
+                        &[0, 1, 2, 3],
+                    )),
                     ..default()
                 },
-                ..default()
-            });
+                DustEmitter,
+            ));
         })
         .insert(Player);
 }
@@ -51,29 +120,15 @@ struct AnimationTimer(Timer);
 
 fn animate_sprite_system(
     time: Res<Time>,
-    mut sprites_to_animate: Query<(&mut AnimationTimer, &mut TextureAtlas)>,
+    mut sprites_to_animate: Query<(&mut AnimationTimer, &mut TextureAtlas, &mut Facing)>,
     actions: Res<Actions>,
 ) {
-    for (mut timer, mut sprite) in &mut sprites_to_animate {
+    for (mut timer, mut sprite, mut facing) in &mut sprites_to_animate {
         timer.0.tick(time.delta());
         if timer.0.finished() {
-            match actions.player_movement {
-                Some(movement) => {
-                    if movement.y < 0. {
-                        sprite.index = (sprite.index + 1) % 6;
-                    } else if movement.y > 0. {
-                        sprite.index = 12 + ((sprite.index + 1) % 6);
-                    } else if movement.x > 0. {
-                        sprite.index = 6 + ((sprite.index + 1) % 6);
-                    } else if movement.x < 0. {
-                        sprite.index = 18 + ((sprite.index + 1) % 6);
-                    } else {
-                        sprite.index = (sprite.index + 1) % 6;
-                    }
-                }
-                None => {
-                    sprite.index = sprite.index;
-                }
+            if let Some(movement) = actions.player_movement {
+                *facing = Facing::from_direction(movement).unwrap_or_default();
+                sprite.index = facing.next_frame(sprite.index);
             }
         }
     }
@@ -83,7 +138,8 @@ fn move_player(
     time: Res<Time>,
     actions: Res<Actions>,
     mut player_query: Query<(&mut Transform, &Handle<Image>), With<Player>>,
-    bounding_query: Query<&Bounding, (With<Parent>, Without<Player>)>,
+    bounding_query: Query<&Bounding, (With<Parent>, Without<Player>, Without<Trigger>)>,
+    broad_phase: Res<BroadPhase>,
 ) {
     if actions.player_movement.is_none() {
         return;
@@ -95,26 +151,61 @@ fn move_player(
         0.0,
     );
     for (mut player_transform, _player_image) in &mut player_query {
-        let mut undo_movement = false;
         player_transform.translation += movement;
-        // TODO: Figure out how to get the correct size from the sprite sheet
-        let player_dimensions = Vec2::new(32.0, 47.0);
-        let mut player_bounds = Aabb2d::new(
-            player_transform.translation.truncate(),
-            player_dimensions / 2.0,
-        );
-        player_bounds = player_bounds.shrink(Vec2::new(10.0, 10.0));
+        let player_bounds = character_bounds(player_transform.translation);
         info!("Player BB: {:?}", player_bounds);
-        for bounding in &bounding_query {
-            // TODO: Generalize to vector
-            let scenery_bounds = bounding.boxes[0];
-            info!("Scenery BB: {:?}", scenery_bounds);
-            if scenery_bounds.intersects(&player_bounds) {
-                undo_movement = true;
-            }
+        if broad_phase.collides(&player_bounds, &bounding_query) {
+            player_transform.translation -= movement;
         }
-        if undo_movement == true {
+    }
+}
+
+fn apply_knockback(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_query: Query<(Entity, &mut Transform, &mut Knockback), With<Player>>,
+    bounding_query: Query<&Bounding, (With<Parent>, Without<Player>, Without<Trigger>)>,
+    broad_phase: Res<BroadPhase>,
+) {
+    for (entity, mut player_transform, mut knockback) in &mut player_query {
+        let movement = (knockback.velocity * time.delta_seconds()).extend(0.0);
+        player_transform.translation += movement;
+        let player_bounds = character_bounds(player_transform.translation);
+        if broad_phase.collides(&player_bounds, &bounding_query) {
             player_transform.translation -= movement;
+            knockback.velocity = Vec2::ZERO;
+        }
+        knockback.velocity *= (-KNOCKBACK_DAMPING * time.delta_seconds()).exp();
+        if knockback.velocity.length() < 10.0 {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
+
+fn kick_up_dust(
+    actions: Res<Actions>,
+    player: Query<&Transform, With<Player>>,
+    ground: Query<(&GlobalTransform, &Tilemap), With<Ground>>,
+    mut dust: Query<&mut ParticleEmitter, With<DustEmitter>>,
+) {
+    let Ok(player) = player.get_single() else {
+        return;
+    };
+    let on_pavement = ground.iter().any(|(transform, tilemap)| {
+        let local = player.translation.truncate() - transform.translation().truncate();
+        tilemap
+            .tile_at(local)
+            .and_then(|position| tilemap.get(position))
+            .is_some()
+    });
+    let rate = if on_pavement && actions.player_movement.is_some() {
+        DUST_RATE
+    } else {
+        0.0
+    };
+    for mut emitter in &mut dust {
+        if emitter.rate != rate {
+            emitter.rate = rate;
         }
     }
 }
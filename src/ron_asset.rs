@@ -0,0 +1,81 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Registers the asset type `A` together with a loader that deserializes it from RON files
+/// Data driven content (NPC routes, dialogues, levels...) is authored in `assets` this way
+pub struct RonAssetPlugin<A> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetPlugin<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        RonAssetPlugin {
+            extensions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A: Asset + DeserializeOwned> Plugin for RonAssetPlugin<A> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<A>()
+            .register_asset_loader(RonAssetLoader::<A> {
+                extensions: self.extensions,
+                _marker: PhantomData,
+            });
+    }
+}
+
+struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> A>,
+}
+
+#[derive(Debug)]
+pub enum RonLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for RonLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonLoaderError::Io(error) => write!(f, "could not read file: {error}"),
+            RonLoaderError::Ron(error) => write!(f, "could not parse RON: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RonLoaderError {}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<A, RonLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(RonLoaderError::Io)?;
+            ron::de::from_bytes(&bytes).map_err(RonLoaderError::Ron)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
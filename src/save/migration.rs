@@ -0,0 +1,140 @@
+use crate::helpe::Facing;
+use crate::save::{NpcState, PlayerState, PropState, SaveData, SaveError, SAVE_VERSION};
+use bevy::math::Vec2;
+use serde::Deserialize;
+
+/// Just enough of any save game version to decide how to read the rest
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// Reads a save game of any supported version and migrates it to the current `SaveData`
+///
+/// Every schema change freezes the types of the previous version in this file and adds a
+/// function converting them into the next version. Old files run through that whole chain.
+pub(super) fn load(contents: &str) -> Result<SaveData, SaveError> {
+    let header: Header = ron::from_str(contents).map_err(SaveError::Deserialize)?;
+    let data = match header.version {
+        1 => v1_to_v2(ron::from_str(contents).map_err(SaveError::Deserialize)?),
+        SAVE_VERSION => ron::from_str(contents).map_err(SaveError::Deserialize)?,
+        version => return Err(SaveError::UnsupportedVersion(version)),
+    };
+    Ok(data)
+}
+
+/// Version 1 also carried the settings, reading it skips that field
+#[derive(Deserialize)]
+struct SaveDataV1 {
+    play_time: f32,
+    player: PlayerStateV1,
+    props: Vec<PropStateV1>,
+    npcs: Vec<NpcStateV1>,
+    flags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PlayerStateV1 {
+    position: Vec2,
+    facing: Facing,
+}
+
+#[derive(Deserialize)]
+struct PropStateV1 {
+    id: String,
+    position: Vec2,
+}
+
+#[derive(Deserialize)]
+struct NpcStateV1 {
+    name: String,
+    position: Vec2,
+    waypoint: usize,
+}
+
+/// Version 2 drops the settings, they are shared by all slots and stored on their own now
+fn v1_to_v2(old: SaveDataV1) -> SaveData {
+    SaveData {
+        version: 2,
+        play_time: old.play_time,
+        player: PlayerState {
+            position: old.player.position,
+            facing: old.player.facing,
+        },
+        props: old
+            .props
+            .into_iter()
+            .map(|prop| PropState {
+                id: prop.id,
+                position: prop.position,
+            })
+            .collect(),
+        npcs: old
+            .npcs
+            .into_iter()
+            .map(|npc| NpcState {
+                name: npc.name,
+                position: npc.position,
+                waypoint: npc.waypoint,
+            })
+            .collect(),
+        flags: old.flags,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The game both fixtures were saved from, once by a version 1 build and once by the current one
+    fn fixture_game() -> SaveData {
+        SaveData {
+            version: SAVE_VERSION,
+            play_time: 754.5,
+            player: PlayerState {
+                position: Vec2::new(-120.0, -340.0),
+                facing: Facing::Left,
+            },
+            props: vec![
+                PropState {
+                    id: "car_blue".to_owned(),
+                    position: Vec2::new(340.0, -500.0),
+                },
+                PropState {
+                    id: "house".to_owned(),
+                    position: Vec2::new(0.0, -200.0),
+                },
+            ],
+            npcs: vec![NpcState {
+                name: "Margaret".to_owned(),
+                position: Vec2::new(620.0, 180.0),
+                waypoint: 2,
+            }],
+            flags: vec!["complimented_roses".to_owned(), "visited_road".to_owned()],
+        }
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        let data = load(include_str!("../../tests/fixtures/saves/v1.ron"));
+        assert_eq!(data.ok(), Some(fixture_game()));
+    }
+
+    #[test]
+    fn reads_the_current_version() {
+        let data = load(include_str!("../../tests/fixtures/saves/v2.ron"));
+        assert_eq!(data.ok(), Some(fixture_game()));
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let written = ron::ser::to_string(&fixture_game()).unwrap();
+        assert_eq!(load(&written).ok(), Some(fixture_game()));
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let result = load("(version: 99, play_time: 1.0)");
+        assert!(matches!(result, Err(SaveError::UnsupportedVersion(99))));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::actions::Actions;
+use crate::dialogue::DialogueEnded;
+use crate::flags::GameFlags;
+use crate::helpe::Facing;
+use crate::npc::{Npc, Patrol};
+use crate::player::Player;
+use crate::scenery::Prop;
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+mod migration;
+mod storage;
+
+pub use storage::SaveDirectory;
+
+/// Version written into new save games, older ones are upgraded by `migration::load`
+pub const SAVE_VERSION: u32 = 2;
+pub const SLOT_COUNT: usize = 3;
+
+const LAST_SLOT_KEY: &str = "last_slot";
+const SETTINGS_KEY: &str = "settings";
+
+pub struct SavePlugin;
+
+/// This plugin writes the game state into save slots and restores it when a slot is loaded
+/// Saving happens with the quick save key and whenever a conversation ends
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveDirectory>()
+            .init_resource::<SaveSlots>()
+            .init_resource::<PlayTime>()
+            .add_systems(Startup, refresh_save_slots)
+            .add_systems(
+                Update,
+                (
+                    apply_pending_load.run_if(resource_exists::<PendingLoad>),
+                    tick_play_time,
+                    save_game.run_if(should_save),
+                )
+                    .chain()
+                    .after(crate::actions::set_button_actions)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaveData {
+    pub version: u32,
+    /// Seconds spent in `GameState::Playing`
+    pub play_time: f32,
+    pub player: PlayerState,
+    pub props: Vec<PropState>,
+    pub npcs: Vec<NpcState>,
+    pub flags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerState {
+    pub position: Vec2,
+    pub facing: Facing,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PropState {
+    pub id: String,
+    pub position: Vec2,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NpcState {
+    pub name: String,
+    pub position: Vec2,
+    pub waypoint: usize,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    UnsupportedVersion(u32),
+    #[cfg(target_arch = "wasm32")]
+    StorageUnavailable,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "could not access save file: {error}"),
+            SaveError::Serialize(error) => write!(f, "could not write save game: {error}"),
+            SaveError::Deserialize(error) => write!(f, "could not read save game: {error}"),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "save game version {version} is not supported")
+            }
+            #[cfg(target_arch = "wasm32")]
+            SaveError::StorageUnavailable => write!(f, "local storage is not available"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// What the menu shows about a filled save slot
+#[derive(Clone, Debug)]
+pub struct SlotSummary {
+    pub play_time: f32,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct SaveSlots {
+    /// The slot the running game is saved to
+    pub active: usize,
+    /// The slot that was saved to most recently, this is what "Continue" loads
+    pub last_used: Option<usize>,
+    pub summaries: [Option<SlotSummary>; SLOT_COUNT],
+}
+
+impl SaveSlots {
+    pub fn first_empty(&self) -> Option<usize> {
+        self.summaries.iter().position(Option::is_none)
+    }
+}
+
+/// Inserted before entering `GameState::Playing` to restore a save game into the fresh world
+#[derive(Resource)]
+pub struct PendingLoad(pub SaveData);
+
+#[derive(Resource, Default)]
+pub struct PlayTime(pub f32);
+
+fn slot_key(slot: usize) -> String {
+    format!("slot_{}", slot + 1)
+}
+
+pub fn read_slot(directory: &SaveDirectory, slot: usize) -> Result<Option<SaveData>, SaveError> {
+    let Some(contents) = storage::read(directory, &slot_key(slot))? else {
+        return Ok(None);
+    };
+    migration::load(&contents).map(Some)
+}
+
+pub fn write_slot(
+    directory: &SaveDirectory,
+    slot: usize,
+    data: &SaveData,
+) -> Result<(), SaveError> {
+    let contents = ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::Serialize)?;
+    storage::write(directory, &slot_key(slot), &contents)?;
+    storage::write(directory, LAST_SLOT_KEY, &slot.to_string())
+}
+
+pub fn read_settings(directory: &SaveDirectory) -> Result<Option<Settings>, SaveError> {
+    let Some(contents) = storage::read(directory, SETTINGS_KEY)? else {
+        return Ok(None);
+    };
+    ron::from_str(&contents)
+        .map(Some)
+        .map_err(SaveError::Deserialize)
+}
+
+pub fn write_settings(directory: &SaveDirectory, settings: &Settings) -> Result<(), SaveError> {
+    let contents = ron::ser::to_string_pretty(settings, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::Serialize)?;
+    storage::write(directory, SETTINGS_KEY, &contents)
+}
+
+pub fn delete_slot(directory: &SaveDirectory, slot: usize) -> Result<(), SaveError> {
+    storage::delete(directory, &slot_key(slot))
+}
+
+fn refresh_save_slots(directory: Res<SaveDirectory>, mut slots: ResMut<SaveSlots>) {
+    for slot in 0..SLOT_COUNT {
+        slots.summaries[slot] = match read_slot(&directory, slot) {
+            Ok(data) => data.map(|data| SlotSummary {
+                play_time: data.play_time,
+            }),
+            Err(error) => {
+                warn!("Ignoring save slot {}: {error}", slot + 1);
+                None
+            }
+        };
+    }
+    slots.last_used = storage::read(&directory, LAST_SLOT_KEY)
+        .ok()
+        .flatten()
+        .and_then(|slot| slot.trim().parse::<usize>().ok())
+        .filter(|slot| slots.summaries.get(*slot).is_some_and(Option::is_some));
+    slots.active = slots.last_used.or(slots.first_empty()).unwrap_or(0);
+}
+
+/// Prepares loading `slot` (or a new game if it is empty) and returns whether that worked
+pub fn select_slot(
+    commands: &mut Commands,
+    directory: &SaveDirectory,
+    slots: &mut SaveSlots,
+    slot: usize,
+) -> bool {
+    match read_slot(directory, slot) {
+        Ok(data) => {
+            slots.active = slot;
+            match data {
+                Some(data) => commands.insert_resource(PendingLoad(data)),
+                None => commands.remove_resource::<PendingLoad>(),
+            }
+            true
+        }
+        Err(error) => {
+            error!("Failed to load save slot {}: {error}", slot + 1);
+            false
+        }
+    }
+}
+
+/// Removes the save game in `slot` and updates the slot summaries
+pub fn clear_slot(directory: &SaveDirectory, slots: &mut SaveSlots, slot: usize) {
+    if let Err(error) = delete_slot(directory, slot) {
+        error!("Failed to delete save slot {}: {error}", slot + 1);
+        return;
+    }
+    slots.summaries[slot] = None;
+    if slots.last_used == Some(slot) {
+        slots.last_used = None;
+    }
+}
+
+fn apply_pending_load(
+    mut commands: Commands,
+    pending: Res<PendingLoad>,
+    mut player: Query<(&mut Transform, &mut Facing, &mut TextureAtlas), With<Player>>,
+    mut props: Query<(&Prop, &mut Transform), Without<Player>>,
+    mut npcs: Query<
+        (&Name, &mut Transform, &mut Patrol),
+        (With<Npc>, Without<Player>, Without<Prop>),
+    >,
+    mut flags: ResMut<GameFlags>,
+    mut play_time: ResMut<PlayTime>,
+) {
+    let data = &pending.0;
+    for (mut transform, mut facing, mut sprite) in &mut player {
+        transform.translation = data.player.position.extend(transform.translation.z);
+        *facing = data.player.facing;
+        sprite.index = facing.first_frame();
+    }
+    for (prop, mut transform) in &mut props {
+        if let Some(state) = data.props.iter().find(|state| state.id == prop.id) {
+            transform.translation = state.position.extend(transform.translation.z);
+        }
+    }
+    for (name, mut transform, mut patrol) in &mut npcs {
+        if let Some(state) = data.npcs.iter().find(|state| state.name == name.as_str()) {
+            transform.translation = state.position.extend(transform.translation.z);
+            patrol.resume_at(state.waypoint);
+        }
+    }
+    *flags = GameFlags::default();
+    for flag in data.flags.iter() {
+        flags.set(flag.clone());
+    }
+    play_time.0 = data.play_time;
+    commands.remove_resource::<PendingLoad>();
+}
+
+fn tick_play_time(time: Res<Time>, mut play_time: ResMut<PlayTime>) {
+    play_time.0 += time.delta_seconds();
+}
+
+fn should_save(actions: Res<Actions>, mut dialogue_ended: EventReader<DialogueEnded>) -> bool {
+    // Conversations are where flags change, so they are a good moment for an autosave
+    actions.quick_save | (dialogue_ended.read().count() > 0)
+}
+
+fn save_game(
+    directory: Res<SaveDirectory>,
+    mut slots: ResMut<SaveSlots>,
+    play_time: Res<PlayTime>,
+    player: Query<(&Transform, &Facing), With<Player>>,
+    props: Query<(&Prop, &Transform)>,
+    npcs: Query<(&Name, &Transform, &Patrol), With<Npc>>,
+    flags: Res<GameFlags>,
+) {
+    let Ok((player_transform, facing)) = player.get_single() else {
+        return;
+    };
+    let data = SaveData {
+        version: SAVE_VERSION,
+        play_time: play_time.0,
+        player: PlayerState {
+            position: player_transform.translation.truncate(),
+            facing: *facing,
+        },
+        props: props
+            .iter()
+            .map(|(prop, transform)| PropState {
+                id: prop.id.clone(),
+                position: transform.translation.truncate(),
+            })
+            .collect(),
+        npcs: npcs
+            .iter()
+            .map(|(name, transform, patrol)| NpcState {
+                name: name.as_str().to_owned(),
+                position: transform.translation.truncate(),
+                waypoint: patrol.current_waypoint(),
+            })
+            .collect(),
+        flags: flags.iter().map(str::to_owned).collect(),
+    };
+    let slot = slots.active;
+    match write_slot(&directory, slot, &data) {
+        Ok(()) => {
+            info!("Saved the game to slot {}", slot + 1);
+            slots.summaries[slot] = Some(SlotSummary {
+                play_time: data.play_time,
+            });
+            slots.last_used = Some(slot);
+        }
+        Err(error) => error!("Failed to save the game to slot {}: {error}", slot + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npc::{NpcDefinition, Waypoint};
+
+    fn waypoint(x: f32, y: f32) -> Waypoint {
+        Waypoint {
+            position: Vec2::new(x, y),
+            pause: 0.0,
+        }
+    }
+
+    #[test]
+    fn loading_version_1_restores_the_world() {
+        let mut app = App::new();
+        app.init_resource::<GameFlags>()
+            .init_resource::<PlayTime>()
+            .add_systems(
+                Update,
+                apply_pending_load.run_if(resource_exists::<PendingLoad>),
+            );
+        app.world.resource_mut::<GameFlags>().set("stale_flag");
+        let data = migration::load(include_str!("../../tests/fixtures/saves/v1.ron"));
+        app.insert_resource(PendingLoad(data.unwrap()));
+
+        let player = app
+            .world
+            .spawn((
+                Transform::from_xyz(0.0, 0.0, 2.0),
+                Facing::Down,
+                TextureAtlas::default(),
+                Player,
+            ))
+            .id();
+        let house = app
+            .world
+            .spawn((Transform::from_xyz(10.0, 10.0, 1.0), Prop::new("house")))
+            .id();
+        let unsaved_prop = app
+            .world
+            .spawn((Transform::from_xyz(5.0, 5.0, 1.0), Prop::new("tree")))
+            .id();
+        let margaret = NpcDefinition {
+            name: "Margaret".to_owned(),
+            speed: 60.0,
+            waypoints: vec![
+                waypoint(0.0, 0.0),
+                waypoint(100.0, 0.0),
+                waypoint(100.0, 100.0),
+            ],
+            dialogue: None,
+        };
+        let npc = app
+            .world
+            .spawn((
+                Transform::from_xyz(0.0, 0.0, 1.5),
+                Name::new("Margaret"),
+                Patrol::new(&margaret),
+                Npc,
+            ))
+            .id();
+
+        app.update();
+
+        let world = &app.world;
+        let translation = |entity| world.get::<Transform>(entity).unwrap().translation;
+        assert_eq!(translation(player), Vec3::new(-120.0, -340.0, 2.0));
+        assert_eq!(world.get::<Facing>(player), Some(&Facing::Left));
+        assert_eq!(
+            world.get::<TextureAtlas>(player).map(|atlas| atlas.index),
+            Some(Facing::Left.first_frame())
+        );
+        assert_eq!(translation(house), Vec3::new(0.0, -200.0, 1.0));
+        assert_eq!(translation(unsaved_prop), Vec3::new(5.0, 5.0, 1.0));
+        assert_eq!(translation(npc), Vec3::new(620.0, 180.0, 1.5));
+        assert_eq!(
+            world.get::<Patrol>(npc).map(Patrol::current_waypoint),
+            Some(2)
+        );
+        let flags = world.resource::<GameFlags>();
+        assert!(flags.is_set("complimented_roses"));
+        assert!(flags.is_set("visited_road"));
+        assert!(!flags.is_set("stale_flag"));
+        assert_eq!(world.resource::<PlayTime>().0, 754.5);
+        assert!(!world.contains_resource::<PendingLoad>());
+    }
+}
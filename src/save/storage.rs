@@ -0,0 +1,93 @@
+use crate::save::SaveError;
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+/// Folder that save games are written to on native platforms
+///
+/// Desktop builds default to the usual per-user data folder and iOS to the app's documents.
+/// Android has no such folder we could find on our own, so the `mobile` crate inserts this
+/// resource with the app's internal storage path. Web builds use local storage instead.
+#[derive(Resource, Clone, Debug)]
+pub struct SaveDirectory(pub PathBuf);
+
+impl Default for SaveDirectory {
+    fn default() -> Self {
+        SaveDirectory(default_save_directory().unwrap_or_else(|| PathBuf::from("saves")))
+    }
+}
+
+fn default_save_directory() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home.map(|home| home.join("Library").join("Application Support"))
+    } else if cfg!(target_os = "ios") {
+        home.map(|home| home.join("Documents"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".local").join("share")))
+    };
+    base.map(|base| base.join("camp_game"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(directory: &SaveDirectory, key: &str) -> Result<Option<String>, SaveError> {
+    match std::fs::read_to_string(directory.0.join(format!("{key}.ron"))) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(SaveError::Io(error)),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(directory: &SaveDirectory, key: &str, contents: &str) -> Result<(), SaveError> {
+    std::fs::create_dir_all(&directory.0).map_err(SaveError::Io)?;
+    // Write next to the old file first, so a crash mid-write can't corrupt the save
+    let path = directory.0.join(format!("{key}.ron"));
+    let temporary = directory.0.join(format!("{key}.ron.tmp"));
+    std::fs::write(&temporary, contents).map_err(SaveError::Io)?;
+    std::fs::rename(temporary, path).map_err(SaveError::Io)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn delete(directory: &SaveDirectory, key: &str) -> Result<(), SaveError> {
+    match std::fs::remove_file(directory.0.join(format!("{key}.ron"))) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(SaveError::Io(error)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, SaveError> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or(SaveError::StorageUnavailable)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(key: &str) -> String {
+    format!("camp_game.{key}")
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(_directory: &SaveDirectory, key: &str) -> Result<Option<String>, SaveError> {
+    local_storage()?
+        .get_item(&storage_key(key))
+        .map_err(|_| SaveError::StorageUnavailable)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(_directory: &SaveDirectory, key: &str, contents: &str) -> Result<(), SaveError> {
+    local_storage()?
+        .set_item(&storage_key(key), contents)
+        .map_err(|_| SaveError::StorageUnavailable)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn delete(_directory: &SaveDirectory, key: &str) -> Result<(), SaveError> {
+    local_storage()?
+        .remove_item(&storage_key(key))
+        .map_err(|_| SaveError::StorageUnavailable)
+}
@@ -1,153 +1,273 @@
-use crate::helpe::{Bounding,Create};
-use crate::loading::TextureAssets;
-use crate::GameState;
-use bevy::math::bounding::Aabb2d;
+use crate::boundary::{Trigger, Wall};
+use crate::helpe::{Bounding, Create};
+use crate::level::{Level, LevelProp};
+use crate::loading::{DataAssets, TextureAssets};
+use crate::particles::{Curve, ParticleEmitter, ParticleTexture};
+use crate::yard::{YardLayout, YardPiece, YARD_WIDTH};
+use crate::{GameState, WorldSpawned};
+use bevy::ecs::system::EntityCommands;
+use bevy::math::bounding::{Aabb2d, BoundingVolume};
 use bevy::prelude::*;
 
 use crate::player::Player;
 
 pub struct SceneryPlugin;
 
+/// Scenery is drawn at half the size of its textures
+pub const SCENERY_SCALE: f32 = 0.5;
+
+/// How far apart in z consecutive level props are drawn
+const PROP_DEPTH: f32 = 0.001;
+
 #[derive(Component)]
 pub struct Scenery;
 
+/// The parent of the props placed in the level file
+#[derive(Component)]
+pub struct LevelProps;
+
+/// Which of `Level::props` the entity was spawned from
+// Only the editor looks it up
+#[cfg_attr(not(feature = "dev"), allow(dead_code))]
+#[derive(Component)]
+pub struct LevelPropIndex(pub usize);
+
+/// Scenery that keeps its state in save games, identified by a name unique in the yard
+#[derive(Component)]
+pub struct Prop {
+    pub id: String,
+}
+
+impl Prop {
+    pub fn new(id: &str) -> Self {
+        Prop { id: id.to_owned() }
+    }
+}
+
 impl Plugin for SceneryPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             OnEnter(GameState::Playing),
-            (spawn_scenery, compute_bounding_boxes.after(spawn_scenery)),
+            spawn_scenery.run_if(not(resource_exists::<WorldSpawned>)),
+        )
+        // Not only while playing, colliders follow props moved in the editor too
+        .add_systems(
+            Update,
+            (compute_bounding_boxes, fit_emitters_to_bounds).chain(),
         );
     }
 }
 
-fn spawn_scenery(mut commands: Commands, textures: Res<TextureAssets>) {
+fn spawn_scenery(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    data: Res<DataAssets>,
+    levels: Res<Assets<Level>>,
+    yard: Option<Res<YardLayout>>,
+) {
+    // The props of the authored yard's level, a generated yard has its own scenery
+    if let (None, Some(level)) = (&yard, levels.get(&data.yard)) {
+        spawn_level_props(&mut commands, None, &textures, &level.props);
+    }
+
     commands
-        .spawn({
-            SpatialBundle {
-                transform: Transform {
-                    translation: Vec3::new(0.0, 0.0, 1.0),
-                    rotation: Quat::IDENTITY,
-                    scale: Vec3::new(0.5, 0.5, 1.0),
-                },
-                ..Default::default()
+        .spawn(scenery_root(0.0))
+        .with_children(|parent| {
+            if let Some(yard) = &yard {
+                for piece in yard.pieces() {
+                    let mut entity = spawn_yard_piece(parent, &textures, yard, piece);
+                    if let Some(id) = piece.prop_id() {
+                        entity.insert(Prop::new(&id));
+                    }
+                }
+                return;
             }
+            // The rest of the authored yard are the props of its level
+            parent.spawn(road_barrier());
+            parent.spawn(road_trigger());
         })
-        .with_children(|parent| {
-            // Trees
-            parent.spawn(Create::tiled_bounded_sprite(
-                textures.tree1.clone(),
-                440.0 / 0.5,
-                0.0,
-                0.0,
-                false,
-                true,
-                15.0,
-            ));
-            // Cars
-            parent.spawn(Create::bounded_sprite(
-                textures.car_right_gray.clone(),
-                340.0 / 0.5,
-                -600.0 / 0.5,
-                0.0,
-            ));
-            parent.spawn(Create::bounded_sprite(
-                textures.car_right_blue.clone(),
-                340.0 / 0.5,
-                -500.0 / 0.5,
-                0.0,
-            ));
-            parent.spawn(Create::bounded_sprite(
-                textures.car_right_red.clone(),
-                340.0 / 0.5,
-                -400.0 / 0.5,
-                0.0,
-            ));
-            // House
+        .insert(Scenery);
+}
+
+/// Spawns one piece of a generated yard under the scenery's root
+pub fn spawn_yard_piece<'a>(
+    parent: &'a mut ChildBuilder,
+    textures: &TextureAssets,
+    yard: &YardLayout,
+    piece: YardPiece,
+) -> EntityCommands<'a> {
+    // Pieces are placed by their centers in world units
+    let local = |bounds: &Aabb2d| bounds.center() / SCENERY_SCALE;
+    match piece {
+        YardPiece::House => {
+            let house = local(&yard.house);
             parent.spawn(Create::bounded_sprite(
                 textures.house.clone(),
+                house.x,
+                house.y,
                 0.0,
-                -200.0 / 0.5,
-                0.0,
-            ));
-            // Fence horizontal
+            ))
+        }
+        YardPiece::Tree(index) => {
+            let tree = local(&yard.trees[index]);
             parent.spawn((
-                SpriteBundle {
-                    texture: textures.fence_horizontal.clone(),
-                    transform: Transform {
-                        translation: Vec3::new(-280.0 / 0.5, -300.0 / 0.5, -0.1),
-                        rotation: Quat::IDENTITY,
-                        scale: Vec3::new(15.0, 2.0, 1.0),
-                    },
-                    ..Default::default()
-                },
-                ImageScaleMode::Tiled {
-                    tile_x: true,
-                    tile_y: false,
-                    stretch_value: 1.0 / 15.0,
-                },
-                Bounding::new(),
-            ));
-            // Fence corner
-            parent.spawn((
-                SpriteBundle {
-                    texture: textures.fence_left_corner.clone(),
-                    transform: Transform {
-                        translation: Vec3::new(-472.0 / 0.5, -300.0 / 0.5, -0.1),
-                        rotation: Quat::IDENTITY,
-                        scale: Vec3::new(1.0, 2.0, 1.0),
-                    },
-                    ..Default::default()
-                },
-                Bounding::new(),
-            ));
-            // Fence vertical
+                Create::bounded_sprite(textures.tree1.clone(), tree.x, tree.y, 0.0),
+                leaf_emitter(textures),
+            ))
+        }
+        YardPiece::Car(index) => {
+            let (color, car) = &yard.cars[index];
+            let car = local(car);
+            parent.spawn(Create::bounded_sprite(
+                color.texture(textures),
+                car.x,
+                car.y,
+                0.0,
+            ))
+        }
+        // Fences are tiled along their length
+        YardPiece::Fence(index) => {
+            let fence = &yard.fences[index];
+            let center = local(&fence.bounds);
+            let texture = if fence.horizontal {
+                textures.fence_horizontal.clone()
+            } else {
+                textures.fence_vertical.clone()
+            };
             parent.spawn(Create::tiled_bounded_sprite(
-                textures.fence_vertical.clone(),
-                -483.0 / 0.5,
-                0.0 / 0.5,
-                -0.2,
-                false,
-                true,
-                60.0,
-            ));
-            // Back fence
-            parent.spawn((
-                SpriteBundle {
-                    texture: textures.fence_horizontal.clone(),
-                    transform: Transform {
-                        translation: Vec3::new(0.0 / 0.5, 730.0 / 0.5, -0.1),
-                        rotation: Quat::IDENTITY,
-                        scale: Vec3::new(40.0, 2.0, 1.0),
-                    },
-                    ..Default::default()
-                },
-                ImageScaleMode::Tiled {
-                    tile_x: true,
-                    tile_y: false,
-                    stretch_value: 1.0 / 40.0,
-                },
-                Bounding::new(),
-            ));
-            // Front fence (invisible and just a barrier)
-            parent.spawn((
-                SpriteBundle {
-                    texture: textures.fence_horizontal.clone(),
-                    transform: Transform {
-                        translation: Vec3::new(0.0 / 0.5, -730.0 / 0.5, -2.0),
-                        rotation: Quat::IDENTITY,
-                        scale: Vec3::new(40.0, 2.0, 1.0),
-                    },
-                    ..Default::default()
-                },
-                ImageScaleMode::Tiled {
-                    tile_x: true,
-                    tile_y: false,
-                    stretch_value: 1.0 / 40.0,
-                },
-                Bounding::new(),
-            ));
-        })
-        .insert(Scenery);
+                texture,
+                center.x,
+                center.y,
+                -0.1,
+                fence.horizontal,
+                !fence.horizontal,
+                fence.tiles,
+            ))
+        }
+        YardPiece::RoadBarrier => parent.spawn(road_barrier()),
+        YardPiece::Road => parent.spawn(road_trigger()),
+    }
+}
+
+/// Keeps the player from walking off the far side of the road, across the whole yard
+fn road_barrier() -> impl Bundle {
+    Wall::bundle(
+        Vec3::new(0.0, -930.0 / SCENERY_SCALE, 0.0),
+        Vec2::new(YARD_WIDTH / 2.0, 16.0),
+    )
+}
+
+/// The road in front of the yard
+fn road_trigger() -> impl Bundle {
+    Trigger::bundle(
+        "road",
+        Vec3::new(0.0, -816.0 / SCENERY_SCALE, 0.0),
+        Vec2::new(YARD_WIDTH / 2.0, 96.0),
+    )
+}
+
+/// Falling leaves, spread over the tree by `fit_emitters_to_bounds`
+fn leaf_emitter(textures: &TextureAssets) -> ParticleEmitter {
+    ParticleEmitter {
+        rate: 0.8,
+        z_offset: 0.5,
+        lifetime: (3.0, 5.0),
+        velocity: (Vec2::new(-15.0, -40.0), Vec2::new(15.0, -25.0)),
+        spin: (-2.0, 2.0),
+        size: Vec2::splat(8.0),
+        color: Curve::new(&[
+            (0.0, Color::rgb(0.45, 0.6, 0.2)),
+            (1.0, Color::rgb(0.6, 0.45, 0.15)),
+        ]),
+        alpha: Curve::new(&[(0.0, 1.0), (0.8, 1.0), (1.0, 0.0)]),
+        texture: Some(ParticleTexture::new(
+            textures.particles.clone(),
+            textures.particles_layout.clone(),
+            &[4, 5, 6, 7],
+        )),
+        ..default()
+    }
+}
+
+/// Spawns the props of a level under `root`, replacing its children,
+/// or under a new `LevelProps` root if there is none yet
+pub fn spawn_level_props(
+    commands: &mut Commands,
+    root: Option<Entity>,
+    textures: &TextureAssets,
+    props: &[LevelProp],
+) {
+    let root = match root {
+        Some(root) => {
+            commands.entity(root).despawn_descendants();
+            root
+        }
+        None => commands
+            .spawn((scenery_root(0.0), Scenery, LevelProps))
+            .id(),
+    };
+    commands.entity(root).with_children(|parent| {
+        for (index, prop) in props.iter().enumerate() {
+            spawn_level_prop(parent, textures, index, prop);
+        }
+    });
+}
+
+/// Spawns a prop of the level, tagged with its index in `Level::props`
+/// Later props are drawn on top of earlier ones
+fn spawn_level_prop(
+    parent: &mut ChildBuilder,
+    textures: &TextureAssets,
+    index: usize,
+    prop: &LevelProp,
+) {
+    let Some(texture) = textures.prop(&prop.texture) else {
+        warn!(
+            "Level prop {index} has an unknown texture '{}'",
+            prop.texture
+        );
+        return;
+    };
+    let mut entity = parent.spawn((
+        SpriteBundle {
+            texture,
+            transform: Transform {
+                translation: (prop.position / SCENERY_SCALE).extend(index as f32 * PROP_DEPTH),
+                rotation: Quat::IDENTITY,
+                scale: prop.scale.extend(1.0),
+            },
+            ..Default::default()
+        },
+        LevelPropIndex(index),
+    ));
+    if prop.tiled {
+        entity.insert(ImageScaleMode::Tiled {
+            tile_x: prop.scale.x > prop.scale.y,
+            tile_y: prop.scale.y > prop.scale.x,
+            stretch_value: 1.0 / prop.scale.max_element(),
+        });
+    }
+    if prop.collision {
+        entity.insert(Bounding::new());
+    }
+    if let Some(id) = &prop.id {
+        entity.insert(Prop::new(id));
+    }
+    if prop.texture == "tree1" {
+        entity.insert(leaf_emitter(textures));
+    }
+}
+
+/// The parent of a yard's scenery, its children are placed at twice their world position
+pub fn scenery_root(x: f32) -> SpatialBundle {
+    SpatialBundle {
+        transform: Transform {
+            translation: Vec3::new(x, 0.0, 1.0),
+            rotation: Quat::IDENTITY,
+            scale: Vec3::new(SCENERY_SCALE, SCENERY_SCALE, 1.0),
+        },
+        ..Default::default()
+    }
 }
 
 fn compute_bounding_boxes(
@@ -155,7 +275,11 @@ fn compute_bounding_boxes(
     scenery_parent: Query<&Transform, (With<Scenery>, Without<Player>)>,
     mut scenery_child: Query<
         (&Parent, &Transform, &Handle<Image>, &mut Bounding),
-        (With<Parent>, Without<Player>),
+        (
+            With<Parent>,
+            Without<Player>,
+            Or<(Added<Bounding>, Changed<Transform>)>,
+        ),
     >,
 ) {
     for (parent, child_transform, image_handle, mut bounding) in &mut scenery_child {
@@ -167,6 +291,18 @@ fn compute_bounding_boxes(
         scaled_image_dimensions *= parent_transform.scale.truncate();
         scaled_image_dimensions *= child_transform.scale.truncate();
         let scenery_bounds = Aabb2d::new(location, scaled_image_dimensions / 2.0);
+        bounding.boxes.clear();
         bounding.boxes.push(scenery_bounds);
     }
 }
+
+/// Spreads emitters on scenery over its whole bounding box
+fn fit_emitters_to_bounds(
+    mut emitters: Query<(&Bounding, &mut ParticleEmitter), Changed<Bounding>>,
+) {
+    for (bounding, mut emitter) in &mut emitters {
+        if let Some(bounds) = bounding.boxes.first() {
+            emitter.spawn_area = bounds.half_size();
+        }
+    }
+}
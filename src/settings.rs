@@ -0,0 +1,99 @@
+use crate::actions::KeyBindings;
+use crate::save::{self, SaveDirectory};
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+
+/// Android and iOS always run fullscreen with the display's refresh rate
+pub const WINDOW_SETTINGS: bool = !cfg!(any(target_os = "android", target_os = "ios"));
+
+pub struct SettingsPlugin;
+
+/// This plugin loads the player's preferences on startup and applies them whenever they change
+/// Audio volumes are applied by `InternalAudioPlugin`, key bindings are read by the actions
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveDirectory>()
+            .init_resource::<Settings>()
+            .add_systems(Startup, load_settings)
+            .add_systems(Update, apply_settings.run_if(resource_changed::<Settings>));
+    }
+}
+
+/// Player preferences, stored once per installation next to the save games
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub ui_scale: f32,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            fullscreen: false,
+            vsync: true,
+            ui_scale: 1.0,
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn music_volume(&self) -> f64 {
+        (self.master_volume * self.music_volume) as f64
+    }
+
+    pub fn sfx_volume(&self) -> f64 {
+        (self.master_volume * self.sfx_volume) as f64
+    }
+}
+
+fn load_settings(directory: Res<SaveDirectory>, mut settings: ResMut<Settings>) {
+    match save::read_settings(&directory) {
+        Ok(Some(stored)) => *settings = stored,
+        Ok(None) => {}
+        Err(error) => warn!("Using default settings: {error}"),
+    }
+}
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut ui_scale: ResMut<UiScale>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if ui_scale.0 != settings.ui_scale {
+        ui_scale.0 = settings.ui_scale;
+    }
+    if !WINDOW_SETTINGS {
+        return;
+    }
+    let Ok(mut window) = window.get_single_mut() else {
+        return;
+    };
+    let mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+    let present_mode = if settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+    // Only touch the window when something changed, every write goes through to winit
+    if window.mode != mode {
+        window.mode = mode;
+    }
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+}
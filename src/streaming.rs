@@ -0,0 +1,235 @@
+use crate::ground::{ambient_zones, Ground, GroundTiles};
+use crate::helpe::Create;
+use crate::level::Level;
+use crate::loading::{AudioAssets, DataAssets, TextureAssets};
+use crate::navigation::{NavGrid, WORLD_MAX, WORLD_MIN};
+use crate::player::Player;
+use crate::scenery::{scenery_root, spawn_yard_piece, Scenery};
+use crate::yard::{Footprints, YardLayout, YardPiece, YardSeed, YARD_WIDTH};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::collections::VecDeque;
+
+/// Yards closer than this to the player, measured between their centers, are spawned
+const LOAD_DISTANCE: f32 = 2400.0;
+/// Yards further away than this are despawned, a bit more than `LOAD_DISTANCE`
+/// so walking back and forth at the edge doesn't spawn the same yard over and over
+const UNLOAD_DISTANCE: f32 = 3000.0;
+/// Entities spawned for streamed yards in a single frame at most
+const SPAWNS_PER_FRAME: usize = 8;
+/// The neighbors of the authored yard are generated from this seed
+const STREET_SEED: u64 = 0x5EED;
+
+pub struct StreamingPlugin;
+
+/// This plugin extends the street with generated yards on both sides of the home yard
+/// Yards are spawned as the player walks towards them and despawned once they are left behind
+/// The `NavGrid` grows and shrinks with them, so click-to-move reaches every loaded yard
+impl Plugin for StreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StreetChunks>().add_systems(
+            Update,
+            (stream_chunks, spawn_queued_pieces)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Something of a streamed yard waiting for its turn to be spawned
+#[derive(Clone, Copy)]
+enum ChunkPiece {
+    Grass,
+    Ground,
+    /// The authored yard's ambient zones, moved over to the streamed one
+    Ambience,
+    Scenery(YardPiece),
+}
+
+/// A yard along the street, the home yard is chunk 0 and is never streamed
+struct LoadedChunk {
+    yard: YardLayout,
+    /// The parent of the yard's scenery, its colliders are the `Bounding`s of its children
+    scenery: Entity,
+    /// Grass and ground, spawned next to the scenery
+    others: Vec<Entity>,
+}
+
+#[derive(Resource, Default)]
+struct StreetChunks {
+    loaded: HashMap<i32, LoadedChunk>,
+    /// Chunks whose yard failed to generate, their seed won't do better the next time
+    empty: HashSet<i32>,
+    queue: VecDeque<(i32, ChunkPiece)>,
+    /// The first and last chunk the `NavGrid` covers, the home yard is always covered
+    covered: (i32, i32),
+}
+
+fn chunk_x(index: i32) -> f32 {
+    index as f32 * YARD_WIDTH
+}
+
+/// Every yard on the street gets its own seed, the same street always has the same yards
+fn chunk_seed(street: u64, index: i32) -> u64 {
+    street ^ (index as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+fn stream_chunks(
+    mut commands: Commands,
+    mut chunks: ResMut<StreetChunks>,
+    seed: Res<YardSeed>,
+    footprints: Option<Res<Footprints>>,
+    mut nav_grid: ResMut<NavGrid>,
+    player: Query<&Transform, With<Player>>,
+) {
+    let (Ok(player), Some(footprints)) = (player.get_single(), footprints) else {
+        return;
+    };
+    let x = player.translation.x;
+
+    // Leave the yards behind that are too far away
+    let far: Vec<i32> = chunks
+        .loaded
+        .keys()
+        .copied()
+        .filter(|&index| (chunk_x(index) - x).abs() > UNLOAD_DISTANCE)
+        .collect();
+    for index in far {
+        if let Some(chunk) = chunks.loaded.remove(&index) {
+            commands.entity(chunk.scenery).despawn_recursive();
+            for entity in chunk.others {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        chunks.queue.retain(|(queued, _)| *queued != index);
+    }
+    cover_loaded_chunks(&mut chunks, &mut nav_grid);
+
+    // Generating a yard takes a while, only the closest one coming into reach is generated per frame
+    let first = ((x - LOAD_DISTANCE) / YARD_WIDTH).ceil() as i32;
+    let last = ((x + LOAD_DISTANCE) / YARD_WIDTH).floor() as i32;
+    let Some(index) = (first..=last)
+        .filter(|index| {
+            *index != 0 && !chunks.loaded.contains_key(index) && !chunks.empty.contains(index)
+        })
+        .min_by(|a, b| (chunk_x(*a) - x).abs().total_cmp(&(chunk_x(*b) - x).abs()))
+    else {
+        return;
+    };
+    let seed = chunk_seed(seed.0.unwrap_or(STREET_SEED), index);
+    let yard = match YardLayout::generate(seed, &footprints) {
+        Ok(yard) => yard,
+        Err(error) => {
+            warn!("Leaving chunk {index} of the street empty, yard {seed} failed: {error}");
+            chunks.empty.insert(index);
+            return;
+        }
+    };
+
+    // Queue the pieces of the yard to be spawned over the next frames
+    let scenery = commands.spawn((scenery_root(chunk_x(index)), Scenery)).id();
+    chunks.queue.push_back((index, ChunkPiece::Grass));
+    chunks.queue.push_back((index, ChunkPiece::Ground));
+    chunks.queue.push_back((index, ChunkPiece::Ambience));
+    for piece in yard.pieces() {
+        chunks.queue.push_back((index, ChunkPiece::Scenery(piece)));
+    }
+    chunks.loaded.insert(
+        index,
+        LoadedChunk {
+            yard,
+            scenery,
+            others: Vec::new(),
+        },
+    );
+    cover_loaded_chunks(&mut chunks, &mut nav_grid);
+}
+
+/// Resizes the `NavGrid` to reach from the first to the last loaded yard
+fn cover_loaded_chunks(chunks: &mut StreetChunks, nav_grid: &mut NavGrid) {
+    let first = chunks.loaded.keys().copied().fold(0, i32::min);
+    let last = chunks.loaded.keys().copied().fold(0, i32::max);
+    if chunks.covered == (first, last) {
+        return;
+    }
+    chunks.covered = (first, last);
+    let min_x = match first {
+        0 => WORLD_MIN.x,
+        _ => chunk_x(first) - YARD_WIDTH / 2.0,
+    };
+    let max_x = match last {
+        0 => WORLD_MAX.x,
+        _ => chunk_x(last) + YARD_WIDTH / 2.0,
+    };
+    nav_grid.resize(Vec2::new(min_x, WORLD_MIN.y), Vec2::new(max_x, WORLD_MAX.y));
+}
+
+/// Spawns the queued pieces of streamed yards, at most `SPAWNS_PER_FRAME` each frame
+fn spawn_queued_pieces(
+    mut commands: Commands,
+    mut chunks: ResMut<StreetChunks>,
+    textures: Res<TextureAssets>,
+    audio_assets: Res<AudioAssets>,
+    data: Res<DataAssets>,
+    levels: Res<Assets<Level>>,
+    ground_tiles: Option<Res<GroundTiles>>,
+) {
+    let StreetChunks { loaded, queue, .. } = &mut *chunks;
+    for (index, piece) in queue.drain(..SPAWNS_PER_FRAME.min(queue.len())) {
+        let Some(chunk) = loaded.get_mut(&index) else {
+            continue;
+        };
+        let x = chunk_x(index);
+        match piece {
+            ChunkPiece::Grass => {
+                let grass = commands
+                    .spawn(Create::tiled_sprite(
+                        textures.grass.clone(),
+                        x,
+                        0.0,
+                        0.0,
+                        true,
+                        true,
+                        30.0,
+                    ))
+                    .id();
+                chunk.others.push(grass);
+            }
+            ChunkPiece::Ground => {
+                let Some(ground_tiles) = &ground_tiles else {
+                    continue;
+                };
+                let layer = chunk.yard.ground();
+                let origin = layer.origin + Vec2::new(x, 0.0);
+                let ground = commands
+                    .spawn((
+                        SpatialBundle::from_transform(Transform::from_translation(
+                            origin.extend(0.1),
+                        )),
+                        ground_tiles.tilemap(&layer),
+                        Ground,
+                    ))
+                    .id();
+                chunk.others.push(ground);
+            }
+            ChunkPiece::Ambience => {
+                let Some(level) = levels.get(&data.yard) else {
+                    continue;
+                };
+                // Despawned along with the scenery, the level's hot reload leaves them alone
+                let zones = ambient_zones(&audio_assets, &level.ambience, Vec2::new(x, 0.0));
+                commands.entity(chunk.scenery).with_children(|parent| {
+                    for zone in zones {
+                        parent.spawn(zone);
+                    }
+                });
+            }
+            ChunkPiece::Scenery(piece) => {
+                commands.entity(chunk.scenery).with_children(|parent| {
+                    spawn_yard_piece(parent, &textures, &chunk.yard, piece);
+                });
+            }
+        }
+    }
+}
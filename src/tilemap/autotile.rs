@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Picks the tile for painted terrain from which of its neighbors are painted too,
+/// see `assets/levels/asphalt.autotile.ron`
+#[derive(Asset, TypePath, Deserialize, Clone)]
+pub struct AutotileRules {
+    /// Tried in order, the first rule whose neighbors match picks the tile
+    rules: Vec<AutotileRule>,
+    /// Tiles for painted terrain that no rule matches
+    fallback: Vec<TileNumber>,
+}
+
+#[derive(Deserialize, Clone)]
+struct AutotileRule {
+    /// Neighbors that have to be painted
+    #[serde(default)]
+    filled: Vec<Neighbor>,
+    /// Neighbors that have to be empty, all others may be either
+    #[serde(default)]
+    empty: Vec<Neighbor>,
+    /// Variants to pick from
+    tiles: Vec<TileNumber>,
+}
+
+/// A tile numbered like the digits in level rows, so `1` is the first tile of the tileset
+/// Files with a `0` fail to load, it would leave a hole in the map
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "usize")]
+struct TileNumber(usize);
+
+impl TryFrom<usize> for TileNumber {
+    type Error = String;
+
+    fn try_from(number: usize) -> Result<Self, String> {
+        match number.checked_sub(1) {
+            Some(index) => Ok(TileNumber(index)),
+            None => Err("tiles are numbered from 1, there is no tile 0".to_owned()),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum Neighbor {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Neighbor {
+    pub const ALL: [Neighbor; 8] = [
+        Neighbor::North,
+        Neighbor::NorthEast,
+        Neighbor::East,
+        Neighbor::SouthEast,
+        Neighbor::South,
+        Neighbor::SouthWest,
+        Neighbor::West,
+        Neighbor::NorthWest,
+    ];
+
+    pub fn offset(self) -> IVec2 {
+        match self {
+            Neighbor::North => IVec2::new(0, 1),
+            Neighbor::NorthEast => IVec2::new(1, 1),
+            Neighbor::East => IVec2::new(1, 0),
+            Neighbor::SouthEast => IVec2::new(1, -1),
+            Neighbor::South => IVec2::new(0, -1),
+            Neighbor::SouthWest => IVec2::new(-1, -1),
+            Neighbor::West => IVec2::new(-1, 0),
+            Neighbor::NorthWest => IVec2::new(-1, 1),
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn mask(neighbors: &[Neighbor]) -> u8 {
+        neighbors
+            .iter()
+            .fold(0, |mask, neighbor| mask | neighbor.bit())
+    }
+}
+
+/// Which of the eight neighbors of `position` are painted, one bit per `Neighbor`
+pub fn neighbor_mask(position: IVec2, is_filled: impl Fn(IVec2) -> bool) -> u8 {
+    Neighbor::ALL
+        .iter()
+        .filter(|neighbor| is_filled(position + neighbor.offset()))
+        .fold(0, |mask, neighbor| mask | neighbor.bit())
+}
+
+impl AutotileRules {
+    /// The tileset index for painted terrain at `position` with the painted neighbors in `mask`
+    /// Variants are chosen by position, so the same map always looks the same
+    pub fn pick(&self, mask: u8, position: UVec2) -> Option<usize> {
+        let tiles = self
+            .rules
+            .iter()
+            .find(|rule| {
+                let filled = Neighbor::mask(&rule.filled);
+                mask & filled == filled && mask & Neighbor::mask(&rule.empty) == 0
+            })
+            .map_or(&self.fallback, |rule| &rule.tiles);
+        if tiles.is_empty() {
+            return None;
+        }
+        let hash = position.x.wrapping_mul(73_856_093) ^ position.y.wrapping_mul(19_349_663);
+        Some(tiles[hash as usize % tiles.len()].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asphalt() -> AutotileRules {
+        ron::from_str(include_str!("../../assets/levels/asphalt.autotile.ron")).unwrap()
+    }
+
+    /// The tiles `rules` pick for a mask over a stretch of positions, as tileset indices
+    fn picks(rules: &AutotileRules, mask: u8) -> Vec<usize> {
+        (0..16)
+            .flat_map(|x| (0..16).map(move |y| UVec2::new(x, y)))
+            .map(|position| rules.pick(mask, position).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn masks_the_painted_neighbors() {
+        let painted = [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(-1, -1)];
+        let mask = neighbor_mask(IVec2::ZERO, |position| painted.contains(&position));
+        let expected = Neighbor::mask(&[Neighbor::East, Neighbor::North, Neighbor::SouthWest]);
+        assert_eq!(mask, expected);
+    }
+
+    #[test]
+    fn picks_west_and_east_edges() {
+        let rules = asphalt();
+        let west_edge = Neighbor::mask(&[Neighbor::East, Neighbor::North, Neighbor::South]);
+        assert!(picks(&rules, west_edge)
+            .iter()
+            .all(|tile| [0, 4].contains(tile)));
+        let east_edge = Neighbor::mask(&[Neighbor::West, Neighbor::North]);
+        assert!(picks(&rules, east_edge)
+            .iter()
+            .all(|tile| [3, 7].contains(tile)));
+    }
+
+    #[test]
+    fn falls_back_without_a_matching_rule() {
+        let rules = asphalt();
+        for mask in [u8::MAX, 0, Neighbor::mask(&[Neighbor::North])] {
+            let tiles = picks(&rules, mask);
+            assert!(tiles.iter().all(|tile| [1, 2, 5, 6].contains(tile)));
+            // Varies between the tiles instead of repeating one of them
+            assert!(tiles.iter().any(|tile| *tile != tiles[0]));
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules: AutotileRules = ron::from_str(
+            "(rules: [(filled: [East], tiles: [2]), (filled: [East, North], tiles: [3])], \
+            fallback: [1])",
+        )
+        .unwrap();
+        let mask = Neighbor::mask(&[Neighbor::East, Neighbor::North]);
+        assert_eq!(rules.pick(mask, UVec2::ZERO), Some(1));
+        assert_eq!(rules.pick(0, UVec2::ZERO), Some(0));
+    }
+
+    #[test]
+    fn rejects_tile_zero() {
+        let rules = ron::from_str::<AutotileRules>("(rules: [(tiles: [0])], fallback: [1])");
+        assert!(rules.is_err());
+        let fallback = ron::from_str::<AutotileRules>("(rules: [], fallback: [0, 1])");
+        assert!(fallback.is_err());
+    }
+}
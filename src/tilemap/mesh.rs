@@ -0,0 +1,62 @@
+use crate::tilemap::{Tilemap, CHUNK_SIZE};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+
+/// A quad for every tile in the chunk at `coords`, or `None` if all of its tiles are empty
+/// Vertices are relative to the map's bottom left corner, so chunks sit at the map's origin
+pub(super) fn chunk_mesh(
+    tilemap: &Tilemap,
+    coords: UVec2,
+    layout: &TextureAtlasLayout,
+) -> Option<Mesh> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    let start = coords * CHUNK_SIZE;
+    let end = (start + UVec2::splat(CHUNK_SIZE)).min(tilemap.size);
+    for y in start.y..end.y {
+        for x in start.x..end.x {
+            let Some(rect) = tilemap
+                .get(UVec2::new(x, y))
+                .and_then(|tile| tilemap.tileset.atlas_indices.get(tile))
+                .and_then(|&index| layout.textures.get(index))
+            else {
+                continue;
+            };
+            let min = Vec2::new(x as f32, y as f32) * tilemap.tile_size;
+            let max = min + Vec2::splat(tilemap.tile_size);
+            // Texture coordinates go down from the top of the image
+            let uv_min = rect.min / layout.size;
+            let uv_max = rect.max / layout.size;
+            let first = positions.len() as u32;
+            positions.extend([
+                [min.x, min.y, 0.0],
+                [max.x, min.y, 0.0],
+                [max.x, max.y, 0.0],
+                [min.x, max.y, 0.0],
+            ]);
+            uvs.extend([
+                [uv_min.x, uv_max.y],
+                [uv_max.x, uv_max.y],
+                [uv_max.x, uv_min.y],
+                [uv_min.x, uv_min.y],
+            ]);
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+    }
+    if positions.is_empty() {
+        return None;
+    }
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    Some(
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices)),
+    )
+}
@@ -0,0 +1,245 @@
+use crate::atlas::{AtlasError, PackedAtlas};
+use crate::ron_asset::RonAssetPlugin;
+use crate::time_of_day::BaseColor;
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::HashSet;
+
+mod autotile;
+mod mesh;
+
+pub use autotile::{AutotileRules, Neighbor};
+
+/// Tiles along each side of a chunk, every chunk is drawn as a single mesh
+pub const CHUNK_SIZE: u32 = 16;
+
+pub struct TilemapPlugin;
+
+/// This plugin draws every `Tilemap` as a grid of chunk meshes
+/// Changing tiles only rebuilds the meshes of the chunks they are in
+impl Plugin for TilemapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<AutotileRules>::new(&["autotile.ron"]))
+            .add_systems(PostUpdate, build_chunks);
+    }
+}
+
+/// The textures a tilemap draws its tiles from
+#[derive(Clone)]
+pub struct Tileset {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    /// The layout's index for every tile of the set
+    pub atlas_indices: Vec<usize>,
+}
+
+impl Tileset {
+    /// A tileset of the images in `tiles`, fails if one of them isn't packed into `atlas`
+    pub fn new(atlas: PackedAtlas, tiles: &[Handle<Image>]) -> Result<Self, AtlasError> {
+        Ok(Tileset {
+            atlas_indices: tiles
+                .iter()
+                .map(|tile| atlas.index(tile))
+                .collect::<Result<_, _>>()?,
+            image: atlas.image,
+            layout: atlas.layout,
+        })
+    }
+}
+
+/// A grid of tiles with its bottom left corner at the entity's position
+/// Tiles are indices into the `Tileset`, positions count from the bottom left
+/// Terrain painted with `paint` gets its tiles picked by the map's `AutotileRules`
+#[derive(Component)]
+pub struct Tilemap {
+    size: UVec2,
+    tile_size: f32,
+    tileset: Tileset,
+    tiles: Vec<Option<usize>>,
+    /// Whether each tile is painted terrain
+    terrain: Vec<bool>,
+    autotile: Option<AutotileRules>,
+    /// Chunks whose mesh doesn't show their tiles yet
+    dirty: HashSet<UVec2>,
+    /// Shared by all chunks, created with the first one
+    material: Option<Handle<ColorMaterial>>,
+}
+
+/// One square of `CHUNK_SIZE` tiles of a tilemap, spawned as its child
+#[derive(Component)]
+struct TilemapChunk {
+    coords: UVec2,
+}
+
+impl Tilemap {
+    /// An empty map of `size` tiles
+    pub fn new(size: UVec2, tile_size: f32, tileset: Tileset) -> Self {
+        let mut tilemap = Tilemap {
+            size,
+            tile_size,
+            tileset,
+            tiles: vec![None; (size.x * size.y) as usize],
+            terrain: vec![false; (size.x * size.y) as usize],
+            autotile: None,
+            dirty: HashSet::new(),
+            material: None,
+        };
+        // Nothing is drawn yet, so every chunk is out of date
+        let count = tilemap.chunk_count();
+        for y in 0..count.y {
+            for x in 0..count.x {
+                tilemap.dirty.insert(UVec2::new(x, y));
+            }
+        }
+        tilemap
+    }
+
+    pub fn with_autotile(mut self, rules: AutotileRules) -> Self {
+        self.autotile = Some(rules);
+        self
+    }
+
+    pub fn get(&self, position: UVec2) -> Option<usize> {
+        self.index(position).and_then(|index| self.tiles[index])
+    }
+
+    /// Changes a tile, positions outside of the map are ignored
+    pub fn set(&mut self, position: UVec2, tile: Option<usize>) {
+        let Some(index) = self.index(position) else {
+            return;
+        };
+        if self.tiles[index] != tile {
+            self.tiles[index] = tile;
+            self.dirty.insert(position / CHUNK_SIZE);
+        }
+    }
+
+    /// Paints terrain on a tile or clears it, its tile and the ones around it are picked again
+    /// so edges and corners follow the shape of the painted area
+    pub fn paint(&mut self, position: UVec2, filled: bool) {
+        let Some(index) = self.index(position) else {
+            return;
+        };
+        if self.terrain[index] == filled {
+            return;
+        }
+        self.terrain[index] = filled;
+        if !filled {
+            self.set(position, None);
+        }
+        let center = position.as_ivec2();
+        for offset in std::iter::once(IVec2::ZERO).chain(Neighbor::ALL.map(Neighbor::offset)) {
+            let neighbor = center + offset;
+            if neighbor.min_element() >= 0 {
+                self.pick_tile(neighbor.as_uvec2());
+            }
+        }
+    }
+
+    fn pick_tile(&mut self, position: UVec2) {
+        let Some(index) = self.index(position) else {
+            return;
+        };
+        if !self.terrain[index] {
+            return;
+        }
+        let Some(rules) = &self.autotile else {
+            return;
+        };
+        let mask =
+            autotile::neighbor_mask(position.as_ivec2(), |neighbor| self.is_filled(neighbor));
+        let tile = rules.pick(mask, position);
+        self.set(position, tile);
+    }
+
+    /// Terrain continues past the edges of the map, so it has no border there
+    fn is_filled(&self, position: IVec2) -> bool {
+        if position.min_element() < 0 {
+            return true;
+        }
+        self.index(position.as_uvec2())
+            .is_none_or(|index| self.terrain[index])
+    }
+
+    /// The tile position at `local`, relative to the map's bottom left corner
+    pub fn tile_at(&self, local: Vec2) -> Option<UVec2> {
+        let position = (local / self.tile_size).floor();
+        if position.x < 0.0 || position.y < 0.0 {
+            return None;
+        }
+        let position = position.as_uvec2();
+        self.index(position).map(|_| position)
+    }
+
+    fn index(&self, position: UVec2) -> Option<usize> {
+        if position.x >= self.size.x || position.y >= self.size.y {
+            return None;
+        }
+        Some((position.y * self.size.x + position.x) as usize)
+    }
+
+    fn chunk_count(&self) -> UVec2 {
+        (self.size + UVec2::splat(CHUNK_SIZE - 1)) / CHUNK_SIZE
+    }
+}
+
+fn build_chunks(
+    mut commands: Commands,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut tilemaps: Query<(Entity, &mut Tilemap, Option<&Children>)>,
+    chunks: Query<(&TilemapChunk, &Mesh2dHandle)>,
+) {
+    for (entity, mut tilemap, children) in &mut tilemaps {
+        if tilemap.dirty.is_empty() {
+            continue;
+        }
+        // Tried again next frame if the atlas isn't there yet
+        let Some(layout) = layouts.get(&tilemap.tileset.layout) else {
+            continue;
+        };
+        let image = tilemap.tileset.image.clone();
+        let material = tilemap
+            .material
+            .get_or_insert_with(|| materials.add(ColorMaterial::from(image)))
+            .clone();
+        let existing: Vec<(Entity, UVec2, Mesh2dHandle)> = children
+            .into_iter()
+            .flatten()
+            .filter_map(|&child| {
+                chunks
+                    .get(child)
+                    .ok()
+                    .map(|(chunk, mesh)| (child, chunk.coords, mesh.clone()))
+            })
+            .collect();
+        let dirty: Vec<UVec2> = tilemap.dirty.drain().collect();
+        for coords in dirty {
+            let mesh = mesh::chunk_mesh(&tilemap, coords, layout);
+            let chunk = existing.iter().find(|(_, chunk, _)| *chunk == coords);
+            match (mesh, chunk) {
+                (Some(mesh), Some((_, _, handle))) => {
+                    meshes.insert(handle.0.id(), mesh);
+                }
+                (Some(mesh), None) => {
+                    let chunk = commands
+                        .spawn((
+                            MaterialMesh2dBundle {
+                                mesh: meshes.add(mesh).into(),
+                                material: material.clone(),
+                                ..default()
+                            },
+                            TilemapChunk { coords },
+                            BaseColor(Color::WHITE),
+                        ))
+                        .id();
+                    commands.entity(entity).add_child(chunk);
+                }
+                // Chunks without any tiles aren't drawn at all
+                (None, Some((chunk, _, _))) => commands.entity(*chunk).despawn(),
+                (None, None) => {}
+            }
+        }
+    }
+}
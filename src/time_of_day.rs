@@ -0,0 +1,261 @@
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Real seconds a whole day in the yard takes
+const DAY_LENGTH: f32 = 600.0;
+/// The hour a new game starts at
+const START_HOUR: f32 = 8.0;
+/// Hours around each phase boundary over which one palette blends into the next
+const TRANSITION_HOURS: f32 = 1.0;
+
+pub struct TimeOfDayPlugin;
+
+/// This plugin moves the clock forward while playing and tints the yard to match
+/// Every sprite and the clear color are lit by the palette of the current `DayPhase`,
+/// and `DayPhaseChanged` is sent whenever a new phase begins
+impl Plugin for TimeOfDayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .init_resource::<SkyDimming>()
+            .add_event::<DayPhaseChanged>()
+            .add_systems(
+                Update,
+                (advance_time, remember_base_colors, tint_scene)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct TimeOfDay {
+    /// From 0 up to 24, wrapping at midnight
+    hour: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay { hour: START_HOUR }
+    }
+}
+
+impl TimeOfDay {
+    pub fn hour(&self) -> f32 {
+        self.hour
+    }
+
+    pub fn set_hour(&mut self, hour: f32) {
+        self.hour = hour.rem_euclid(24.0);
+    }
+
+    pub fn phase(&self) -> DayPhase {
+        DayPhase::at(self.hour)
+    }
+}
+
+/// How much darker than the time of day alone the yard is, from 0 to 1, e.g. under rain clouds
+#[derive(Resource, Default)]
+pub struct SkyDimming(pub f32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DayPhase {
+    Dawn,
+    Day,
+    Dusk,
+    Night,
+}
+
+impl DayPhase {
+    pub fn start_hour(self) -> f32 {
+        match self {
+            DayPhase::Dawn => 5.0,
+            DayPhase::Day => 7.0,
+            DayPhase::Dusk => 18.0,
+            DayPhase::Night => 20.0,
+        }
+    }
+
+    pub fn at(hour: f32) -> Self {
+        [
+            DayPhase::Night,
+            DayPhase::Dusk,
+            DayPhase::Day,
+            DayPhase::Dawn,
+        ]
+        .into_iter()
+        .find(|phase| hour >= phase.start_hour())
+        // Before dawn it is still the night that began the evening before
+        .unwrap_or(DayPhase::Night)
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            DayPhase::Dawn => DayPhase::Day,
+            DayPhase::Day => DayPhase::Dusk,
+            DayPhase::Dusk => DayPhase::Night,
+            DayPhase::Night => DayPhase::Dawn,
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        match self {
+            DayPhase::Dawn => DayPhase::Night,
+            DayPhase::Day => DayPhase::Dawn,
+            DayPhase::Dusk => DayPhase::Day,
+            DayPhase::Night => DayPhase::Dusk,
+        }
+    }
+
+    fn palette(self) -> Palette {
+        match self {
+            DayPhase::Dawn => Palette {
+                tint: Color::rgb(1.0, 0.85, 0.75),
+                clear: Color::rgb(0.45, 0.38, 0.4),
+            },
+            DayPhase::Day => Palette {
+                tint: Color::WHITE,
+                clear: Color::rgb(0.4, 0.4, 0.4),
+            },
+            DayPhase::Dusk => Palette {
+                tint: Color::rgb(0.95, 0.7, 0.6),
+                clear: Color::rgb(0.35, 0.27, 0.3),
+            },
+            DayPhase::Night => Palette {
+                tint: Color::rgb(0.4, 0.45, 0.7),
+                clear: Color::rgb(0.08, 0.09, 0.16),
+            },
+        }
+    }
+}
+
+/// Sent when the clock crosses into a new phase of the day
+#[derive(Event, Debug)]
+pub struct DayPhaseChanged {
+    pub phase: DayPhase,
+}
+
+#[derive(Clone, Copy)]
+struct Palette {
+    /// Multiplied with the color of every sprite
+    tint: Color,
+    clear: Color,
+}
+
+impl Palette {
+    fn lerp(self, other: Palette, t: f32) -> Palette {
+        let lerp = |from: Color, to: Color| {
+            Color::rgba_from_array(from.rgba_to_vec4().lerp(to.rgba_to_vec4(), t))
+        };
+        Palette {
+            tint: lerp(self.tint, other.tint),
+            clear: lerp(self.clear, other.clear),
+        }
+    }
+
+    fn dimmed(self, dimming: f32) -> Palette {
+        let light = 1.0 - dimming.clamp(0.0, 1.0);
+        let dim = |color: Color| {
+            let [r, g, b, a] = color.as_rgba_f32();
+            Color::rgba(r * light, g * light, b * light, a)
+        };
+        Palette {
+            tint: dim(self.tint),
+            clear: dim(self.clear),
+        }
+    }
+
+    /// The palette at `hour`, blending between phases close to their boundaries
+    fn at(hour: f32) -> Palette {
+        let phase = DayPhase::at(hour);
+        let half = TRANSITION_HOURS / 2.0;
+        let since_start = (hour - phase.start_hour()).rem_euclid(24.0);
+        let until_end = (phase.next().start_hour() - hour).rem_euclid(24.0);
+        if since_start < half {
+            let t = 0.5 + since_start / TRANSITION_HOURS;
+            phase.previous().palette().lerp(phase.palette(), t)
+        } else if until_end < half {
+            let t = 0.5 - until_end / TRANSITION_HOURS;
+            phase.palette().lerp(phase.next().palette(), t)
+        } else {
+            phase.palette()
+        }
+    }
+}
+
+/// The color a sprite or mesh was given before the time of day tinted it
+/// Systems that animate the color of a sprite write it here, a color written to the `Sprite`
+/// instead is taken as its new base color in the next frame
+#[derive(Component)]
+pub struct BaseColor(pub Color);
+
+/// The color `tint_scene` last gave a sprite, any other color was set by another system
+#[derive(Component)]
+pub(crate) struct Tinted(Color);
+
+fn advance_time(
+    time: Res<Time>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut phase_changed: EventWriter<DayPhaseChanged>,
+) {
+    let previous = time_of_day.phase();
+    let hour = time_of_day.hour() + time.delta_seconds() * 24.0 / DAY_LENGTH;
+    time_of_day.set_hour(hour);
+    let phase = time_of_day.phase();
+    if phase != previous {
+        info!("{previous:?} turns into {phase:?}");
+        phase_changed.send(DayPhaseChanged { phase });
+    }
+}
+
+fn remember_base_colors(
+    mut commands: Commands,
+    sprites: Query<(Entity, &Sprite, Has<BaseColor>), Without<Tinted>>,
+) {
+    for (entity, sprite, has_base_color) in &sprites {
+        let mut entity = commands.entity(entity);
+        entity.insert(Tinted(sprite.color));
+        if !has_base_color {
+            entity.insert(BaseColor(sprite.color));
+        }
+    }
+}
+
+pub(crate) fn tint_scene(
+    time_of_day: Res<TimeOfDay>,
+    dimming: Res<SkyDimming>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sprites: Query<(&mut Sprite, &mut BaseColor, &mut Tinted)>,
+    meshes: Query<(&Handle<ColorMaterial>, &BaseColor)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let palette = Palette::at(time_of_day.hour()).dimmed(dimming.0);
+    // Only write what differs, most of the day the palette stands still
+    if clear_color.0 != palette.clear {
+        clear_color.0 = palette.clear;
+    }
+    let tint = palette.tint.rgba_to_vec4();
+    for (mut sprite, mut base, mut tinted) in &mut sprites {
+        // Another system colored the sprite, keep its color and tint that from now on
+        if sprite.color != tinted.0 {
+            base.0 = sprite.color;
+        }
+        let color = Color::rgba_from_array(base.0.rgba_to_vec4() * tint);
+        if sprite.color != color {
+            sprite.color = color;
+        }
+        tinted.0 = color;
+    }
+    // Meshes like the tilemap's chunks are tinted through their material
+    for (handle, base) in &meshes {
+        let color = Color::rgba_from_array(base.0.rgba_to_vec4() * tint);
+        if materials
+            .get(handle)
+            .is_some_and(|material| material.color != color)
+        {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::audio::{Falloff, FalloffCurve, PlaySfx, SfxId, SpatialEmitter};
+use crate::helpe::{character_bounds, Bounding};
+use crate::loading::{AudioAssets, TextureAssets};
+use crate::particles::{Curve, ParticleEmitter, ParticleTexture};
+use crate::player::{GameCamera, Knockback, Player};
+use crate::time_of_day::{DayPhase, DayPhaseChanged};
+use crate::GameState;
+use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
+use bevy::prelude::*;
+use rand::prelude::*;
+
+pub struct TrafficPlugin;
+
+/// The road runs along the bottom of every yard on the street (see `ground::spawn_ground`)
+pub const LANES: [f32; 2] = [-776.0, -856.0];
+/// Cars enter the road this far left of the camera view, so they don't pop in
+const SPAWN_MARGIN: f32 = 200.0;
+/// Cars leave the road this far outside of the camera view, further out than `SPAWN_MARGIN`
+/// so a car that just entered isn't removed again when the player walks to the right
+const DESPAWN_MARGIN: f32 = 600.0;
+
+const CAR_SCALE: f32 = 0.5;
+const CAR_SPEED_RANGE: (f32, f32) = (120.0, 280.0);
+const SPAWN_INTERVAL_RANGE: (f32, f32) = (1.5, 4.0);
+/// Cars come this many times less often at night
+const NIGHT_INTERVAL_FACTOR: f32 = 3.0;
+const MIN_GAP: f32 = 200.0;
+const KNOCKBACK_SPEED: f32 = 700.0;
+
+#[derive(Component)]
+pub struct Car {
+    pub speed: f32,
+    /// Index into `LANES` of the lane the car drives on
+    pub lane: usize,
+}
+
+#[derive(Resource)]
+struct TrafficSpawner {
+    timer: Timer,
+    /// Multiplied with the time between two cars, changes with the time of day
+    interval_factor: f32,
+}
+
+impl Default for TrafficSpawner {
+    fn default() -> Self {
+        TrafficSpawner {
+            timer: Timer::from_seconds(SPAWN_INTERVAL_RANGE.0, TimerMode::Once),
+            interval_factor: 1.0,
+        }
+    }
+}
+
+/// This plugin drives cars along the road in front of the yards
+/// Cars only exist around the camera view, so the traffic follows the player along the street
+/// Traffic is only simulated during the State `GameState::Playing`
+impl Plugin for TrafficPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrafficSpawner>().add_systems(
+            Update,
+            (
+                follow_day_phase,
+                spawn_cars,
+                drive_cars,
+                update_car_bounds,
+                knock_back_player,
+                despawn_cars,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+fn follow_day_phase(
+    mut phase_changed: EventReader<DayPhaseChanged>,
+    mut spawner: ResMut<TrafficSpawner>,
+) {
+    for event in phase_changed.read() {
+        spawner.interval_factor = match event.phase {
+            DayPhase::Night => NIGHT_INTERVAL_FACTOR,
+            _ => 1.0,
+        };
+    }
+}
+
+fn spawn_cars(
+    mut commands: Commands,
+    time: Res<Time>,
+    textures: Res<TextureAssets>,
+    audio_assets: Res<AudioAssets>,
+    mut spawner: ResMut<TrafficSpawner>,
+    camera: Query<(&GlobalTransform, &OrthographicProjection), With<GameCamera>>,
+    cars: Query<(&Transform, &Car)>,
+) {
+    spawner.timer.tick(time.delta());
+    if !spawner.timer.finished() {
+        return;
+    }
+    let Some(view) = camera_view(&camera) else {
+        return;
+    };
+    let start_x = view.min.x - SPAWN_MARGIN;
+    let mut rng = thread_rng();
+    let lane = rng.gen_range(0..LANES.len());
+    let lane_is_free = cars.iter().all(|(transform, car)| {
+        car.lane != lane || (transform.translation.x - start_x).abs() > MIN_GAP
+    });
+    if lane_is_free {
+        let texture = [
+            &textures.car_right_blue,
+            &textures.car_right_red,
+            &textures.car_right_gray,
+        ]
+        .choose(&mut rng)
+        .map(|handle| (*handle).clone())
+        .unwrap_or_default();
+        commands
+            .spawn((
+                SpriteBundle {
+                    texture,
+                    transform: Transform {
+                        translation: Vec3::new(start_x, LANES[lane], 1.0),
+                        rotation: Quat::IDENTITY,
+                        scale: Vec3::new(CAR_SCALE, CAR_SCALE, 1.0),
+                    },
+                    ..Default::default()
+                },
+                Bounding::new(),
+                Car {
+                    speed: rng.gen_range(CAR_SPEED_RANGE.0..CAR_SPEED_RANGE.1),
+                    lane,
+                },
+                SpatialEmitter {
+                    source: audio_assets.engine.clone(),
+                    volume: 0.4,
+                    falloff: Falloff {
+                        min_distance: 150.0,
+                        max_distance: 900.0,
+                        curve: FalloffCurve::Quadratic,
+                    },
+                },
+            ))
+            .with_children(|car| {
+                // Exhaust from the tail pipe, at the car's rear on the left
+                car.spawn((
+                    SpatialBundle::from_transform(Transform::from_xyz(-70.0, -12.0, -0.2)),
+                    ParticleEmitter {
+                        rate: 10.0,
+                        lifetime: (0.5, 0.9),
+                        velocity: (Vec2::new(-50.0, 0.0), Vec2::new(-20.0, 15.0)),
+                        gravity: Vec2::new(0.0, 20.0),
+                        spin: (-1.0, 1.0),
+                        size: Vec2::splat(8.0),
+                        color: Curve::constant(Color::rgb(0.6, 0.6, 0.6)),
+                        alpha: Curve::new(&[(0.0, 0.5), (1.0, 0.0)]),
+                        texture: Some(ParticleTexture::new(
+                            textures.particles.clone(),
+                            textures.particles_layout.clone(),
+                            &[0, 1, 2, 3],
+                        )),
+                        ..default()
+                    },
+                ));
+            });
+    }
+    spawner.timer = Timer::from_seconds(
+        rng.gen_range(SPAWN_INTERVAL_RANGE.0..SPAWN_INTERVAL_RANGE.1) * spawner.interval_factor,
+        TimerMode::Once,
+    );
+}
+
+/// Cars drive at their own speed until they catch up with the car ahead in their lane,
+/// then they keep `MIN_GAP` behind it
+fn drive_cars(time: Res<Time>, mut cars: Query<(&mut Transform, &Car)>) {
+    let mut by_lane: Vec<_> = cars.iter_mut().collect();
+    // Front to back, so the car ahead has already moved
+    by_lane.sort_by(|(a_transform, a), (b_transform, b)| {
+        let (a_x, b_x) = (a_transform.translation.x, b_transform.translation.x);
+        a.lane.cmp(&b.lane).then(b_x.total_cmp(&a_x))
+    });
+    let mut ahead: Option<(usize, f32)> = None;
+    for (transform, car) in &mut by_lane {
+        let x = transform.translation.x;
+        let mut next = x + car.speed * time.delta_seconds();
+        if let Some((lane, ahead_x)) = ahead {
+            if lane == car.lane {
+                // Never backwards, a car closer than the gap just waits
+                next = next.min(ahead_x - MIN_GAP).max(x);
+            }
+        }
+        transform.translation.x = next;
+        ahead = Some((car.lane, next));
+    }
+}
+
+fn update_car_bounds(
+    images: Res<Assets<Image>>,
+    mut cars: Query<(&Transform, &Handle<Image>, &mut Bounding), With<Car>>,
+) {
+    for (transform, image_handle, mut bounding) in &mut cars {
+        let Some(image) = images.get(image_handle) else {
+            continue;
+        };
+        let dimensions = image.size().as_vec2() * transform.scale.truncate();
+        bounding.boxes.clear();
+        bounding.boxes.push(Aabb2d::new(
+            transform.translation.truncate(),
+            dimensions / 2.0,
+        ));
+    }
+}
+
+fn knock_back_player(
+    mut commands: Commands,
+    player: Query<(Entity, &Transform), (With<Player>, Without<Knockback>)>,
+    cars: Query<&Bounding, With<Car>>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    let Ok((player_entity, player_transform)) = player.get_single() else {
+        return;
+    };
+    let player_bounds = character_bounds(player_transform.translation);
+    for bounding in &cars {
+        let Some(car_bounds) = bounding.boxes.first() else {
+            continue;
+        };
+        if car_bounds.intersects(&player_bounds) {
+            // Cars only drive to the right, so always throw the player forward and off the lane
+            let away = (player_bounds.center() - car_bounds.center()).normalize_or_zero();
+            let direction = Vec2::new(1.0, away.y.signum()).normalize();
+            commands.entity(player_entity).insert(Knockback {
+                velocity: direction * KNOCKBACK_SPEED,
+            });
+            sfx.send(PlaySfx::new(SfxId::Flying).at(player_bounds.center()));
+            return;
+        }
+    }
+}
+
+fn despawn_cars(
+    mut commands: Commands,
+    camera: Query<(&GlobalTransform, &OrthographicProjection), With<GameCamera>>,
+    cars: Query<(Entity, &Transform), With<Car>>,
+) {
+    let Some(view) = camera_view(&camera) else {
+        return;
+    };
+    for (entity, transform) in &cars {
+        let x = transform.translation.x;
+        if x < view.min.x - DESPAWN_MARGIN || x > view.max.x + DESPAWN_MARGIN {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// The part of the world the game camera shows
+fn camera_view(
+    camera: &Query<(&GlobalTransform, &OrthographicProjection), With<GameCamera>>,
+) -> Option<Rect> {
+    let (transform, projection) = camera.get_single().ok()?;
+    Some(Rect::from_center_size(
+        transform.translation().truncate(),
+        projection.area.size(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn faster_car_stays_behind_the_car_ahead() {
+        let mut app = App::new();
+        app.init_resource::<Time>().add_systems(Update, drive_cars);
+        let slow = app
+            .world
+            .spawn((
+                Transform::from_xyz(0.0, LANES[0], 1.0),
+                Car {
+                    speed: CAR_SPEED_RANGE.0,
+                    lane: 0,
+                },
+            ))
+            .id();
+        let fast = app
+            .world
+            .spawn((
+                Transform::from_xyz(-300.0, LANES[0], 1.0),
+                Car {
+                    speed: CAR_SPEED_RANGE.1,
+                    lane: 0,
+                },
+            ))
+            .id();
+        let other_lane = app
+            .world
+            .spawn((
+                Transform::from_xyz(-100.0, LANES[1], 1.0),
+                Car {
+                    speed: CAR_SPEED_RANGE.1,
+                    lane: 1,
+                },
+            ))
+            .id();
+        for _ in 0..100 {
+            app.world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(50));
+            app.update();
+            let x = |entity| app.world.get::<Transform>(entity).unwrap().translation.x;
+            assert!(x(slow) - x(fast) >= MIN_GAP - 0.01);
+        }
+        let x = |entity| app.world.get::<Transform>(entity).unwrap().translation.x;
+        assert!((x(slow) - CAR_SPEED_RANGE.0 * 5.0).abs() < 0.01);
+        // Not held up by the slow car in the other lane
+        assert!(x(other_lane) > x(slow));
+    }
+}
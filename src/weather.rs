@@ -0,0 +1,289 @@
+use crate::audio::Soundtrack;
+use crate::loading::TextureAssets;
+use crate::particles::{Curve, ParticleEmitter, ParticleTexture};
+use crate::player::GameCamera;
+use crate::time_of_day::SkyDimming;
+use crate::{GameState, WorldSpawned};
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Seconds any weather lasts before it may change
+const DURATION_RANGE: (f32, f32) = (40.0, 120.0);
+/// How fast the sky darkens or brightens towards the weather's dimming, per second
+const DIMMING_SPEED: f32 = 0.1;
+/// Particles are spawned this far outside of the camera view so they don't pop in
+const VIEW_MARGIN: f32 = 50.0;
+/// Drawn above everything else in the yard
+const PARTICLE_Z: f32 = 5.0;
+
+const RAIN_COLOR: Color = Color::rgba(0.7, 0.75, 0.9, 0.6);
+const RAIN_VELOCITY: Vec2 = Vec2::new(-80.0, -900.0);
+const LEAF_SPEED_RANGE: (f32, f32) = (250.0, 450.0);
+
+pub struct WeatherPlugin;
+
+/// This plugin changes the weather from time to time while playing
+/// Rain and wind fill the camera view with particles, clouds darken the yard through `SkyDimming`
+/// and rain and wind switch to the playlist named like them
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeatherState>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                spawn_weather_emitters.run_if(not(resource_exists::<WorldSpawned>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    advance_weather,
+                    // Coming back from the menu or the editor resets the playlist to the state's
+                    follow_weather
+                        .run_if(
+                            resource_changed::<WeatherState>.or_else(state_changed::<GameState>),
+                        )
+                        .after(crate::audio::follow_game_state),
+                    dim_sky,
+                    aim_weather_emitters,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Weather {
+    Clear,
+    Overcast,
+    Rain,
+    Wind,
+}
+
+impl Weather {
+    /// The weathers this one can turn into, with how likely each of them is
+    fn transitions(self) -> &'static [(Weather, u32)] {
+        match self {
+            Weather::Clear => &[(Weather::Overcast, 6), (Weather::Wind, 4)],
+            Weather::Overcast => &[(Weather::Clear, 4), (Weather::Rain, 4), (Weather::Wind, 2)],
+            Weather::Rain => &[(Weather::Overcast, 7), (Weather::Wind, 3)],
+            Weather::Wind => &[(Weather::Clear, 5), (Weather::Overcast, 5)],
+        }
+    }
+
+    /// How much the clouds darken the yard
+    fn dimming(self) -> f32 {
+        match self {
+            Weather::Clear => 0.0,
+            Weather::Overcast => 0.15,
+            Weather::Rain => 0.3,
+            Weather::Wind => 0.05,
+        }
+    }
+
+    /// The playlist heard during this weather, the one of the game state if `None`
+    fn playlist(self) -> Option<&'static str> {
+        match self {
+            Weather::Rain => Some("Rain"),
+            Weather::Wind => Some("Wind"),
+            Weather::Clear | Weather::Overcast => None,
+        }
+    }
+
+    /// Particles spawned per second while this weather lasts
+    fn particle_rate(self) -> f32 {
+        match self {
+            Weather::Rain => 120.0,
+            Weather::Wind => 6.0,
+            Weather::Clear | Weather::Overcast => 0.0,
+        }
+    }
+}
+
+/// The weather and when it changes next
+/// All changes are drawn from its own generator, so the same seed always gives the same weather
+#[derive(Resource)]
+pub struct WeatherState {
+    weather: Weather,
+    /// Seconds until the weather may change
+    remaining: f32,
+    rng: StdRng,
+}
+
+impl Default for WeatherState {
+    fn default() -> Self {
+        WeatherState::from_seed(thread_rng().gen())
+    }
+}
+
+impl WeatherState {
+    /// Starts out clear
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        WeatherState {
+            weather: Weather::Clear,
+            remaining: rng.gen_range(DURATION_RANGE.0..DURATION_RANGE.1),
+            rng,
+        }
+    }
+
+    pub fn weather(&self) -> Weather {
+        self.weather
+    }
+
+    /// Moves the clock forward by `seconds` and returns the new weather if it changed
+    pub fn advance(&mut self, seconds: f32) -> Option<Weather> {
+        self.remaining -= seconds;
+        if self.remaining > 0.0 {
+            return None;
+        }
+        self.remaining = self.rng.gen_range(DURATION_RANGE.0..DURATION_RANGE.1);
+        let next = self
+            .weather
+            .transitions()
+            .choose_weighted(&mut self.rng, |(_, weight)| *weight)
+            .map_or(self.weather, |(weather, _)| *weather);
+        self.weather = next;
+        Some(next)
+    }
+}
+
+/// Follows the camera view to fill it with particles when the weather calls for them
+#[derive(Component, Clone, Copy, PartialEq)]
+enum WeatherEmitter {
+    Rain,
+    Leaves,
+}
+
+fn advance_weather(time: Res<Time>, mut weather: ResMut<WeatherState>) {
+    // Only marks the resource as changed when the weather actually changes
+    let seconds = time.delta_seconds();
+    if let Some(next) = weather.bypass_change_detection().advance(seconds) {
+        info!("The weather turns {next:?}");
+        weather.set_changed();
+    }
+}
+
+fn follow_weather(weather: Res<WeatherState>, mut soundtrack: ResMut<Soundtrack>) {
+    let playlist = weather
+        .weather()
+        .playlist()
+        .or(GameState::Playing.playlist());
+    if soundtrack.playlist.as_deref() != playlist {
+        soundtrack.playlist = playlist.map(str::to_owned);
+    }
+}
+
+fn dim_sky(time: Res<Time>, weather: Res<WeatherState>, mut dimming: ResMut<SkyDimming>) {
+    let target = weather.weather().dimming();
+    let step = DIMMING_SPEED * time.delta_seconds();
+    let next = if dimming.0 < target {
+        (dimming.0 + step).min(target)
+    } else {
+        (dimming.0 - step).max(target)
+    };
+    if dimming.0 != next {
+        dimming.0 = next;
+    }
+}
+
+fn spawn_weather_emitters(mut commands: Commands, textures: Res<TextureAssets>) {
+    let fall_angle = RAIN_VELOCITY.y.atan2(RAIN_VELOCITY.x) + std::f32::consts::FRAC_PI_2;
+    commands.spawn((
+        SpatialBundle::default(),
+        ParticleEmitter {
+            velocity: (RAIN_VELOCITY, RAIN_VELOCITY),
+            rotation: (fall_angle, fall_angle),
+            size: Vec2::new(2.0, 14.0),
+            color: Curve::constant(RAIN_COLOR),
+            ..default()
+        },
+        WeatherEmitter::Rain,
+    ));
+    commands.spawn((
+        SpatialBundle::default(),
+        ParticleEmitter {
+            velocity: (
+                Vec2::new(LEAF_SPEED_RANGE.0, -60.0),
+                Vec2::new(LEAF_SPEED_RANGE.1, 20.0),
+            ),
+            spin: (-6.0, 6.0),
+            size: Vec2::splat(8.0),
+            color: Curve::new(&[
+                (0.0, Color::rgb(0.45, 0.6, 0.2)),
+                (1.0, Color::rgb(0.6, 0.35, 0.1)),
+            ]),
+            texture: Some(ParticleTexture::new(
+                textures.particles.clone(),
+                textures.particles_layout.clone(),
+                &[4, 5, 6, 7],
+            )),
+            ..default()
+        },
+        WeatherEmitter::Leaves,
+    ));
+}
+
+/// Rain falls from just above the view until it is below it,
+/// leaves blow in from its left edge until they leave on the right
+fn aim_weather_emitters(
+    weather: Res<WeatherState>,
+    camera: Query<(&GlobalTransform, &OrthographicProjection), With<GameCamera>>,
+    mut emitters: Query<(&WeatherEmitter, &mut ParticleEmitter, &mut Transform)>,
+) {
+    let Ok((camera, projection)) = camera.get_single() else {
+        return;
+    };
+    let center = camera.translation().truncate();
+    let view = Rect::from_center_size(center, projection.area.size()).inset(VIEW_MARGIN);
+    let rate = weather.weather().particle_rate();
+    for (kind, mut emitter, mut transform) in &mut emitters {
+        let active = match kind {
+            WeatherEmitter::Rain => weather.weather() == Weather::Rain,
+            WeatherEmitter::Leaves => weather.weather() == Weather::Wind,
+        };
+        emitter.rate = if active { rate } else { 0.0 };
+        let position = match kind {
+            WeatherEmitter::Rain => {
+                // Reaches further right so the drift to the left still covers the whole view
+                let fall_time = view.height() / -RAIN_VELOCITY.y;
+                let drift = -RAIN_VELOCITY.x * fall_time;
+                emitter.lifetime = (fall_time, fall_time);
+                emitter.spawn_area = Vec2::new((view.width() + drift) / 2.0, 0.0);
+                Vec2::new(center.x + drift / 2.0, view.max.y)
+            }
+            WeatherEmitter::Leaves => {
+                let cross_time = view.width() / LEAF_SPEED_RANGE.0;
+                emitter.lifetime = (cross_time, cross_time);
+                emitter.spawn_area = Vec2::new(0.0, view.height() / 2.0);
+                Vec2::new(view.min.x, center.y)
+            }
+        };
+        transform.translation = position.extend(PARTICLE_Z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The weather after every step of `seconds`, with whether it changed in that step
+    fn forecast(seed: u64, seconds: f32, steps: usize) -> Vec<(Weather, bool)> {
+        let mut state = WeatherState::from_seed(seed);
+        (0..steps)
+            .map(|_| {
+                let changed = state.advance(seconds).is_some();
+                (state.weather(), changed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_weather() {
+        for seed in [0, 7, 1234, u64::MAX] {
+            let first = forecast(seed, 5.0, 2000);
+            assert_eq!(first, forecast(seed, 5.0, 2000), "seed {seed}");
+            assert!(first.iter().any(|(_, changed)| *changed), "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use crate::helpe::character_bounds;
+use crate::level::TileLayer;
+use crate::loading::TextureAssets;
+use crate::navigation::{NavGrid, CELL_SIZE, WORLD_MAX, WORLD_MIN};
+use crate::scenery::SCENERY_SCALE;
+use crate::GameState;
+use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+/// The ground grid of generated yards, lined up with `assets/levels/yard.level.ron`
+const GROUND_ORIGIN: Vec2 = Vec2::new(-1920.0, -912.0);
+const TILE_SIZE: f32 = 48.0;
+const GROUND_COLUMNS: u32 = 80;
+/// The road along the bottom of the ground, where traffic drives
+const ROAD_ROWS: u32 = 4;
+const DRIVEWAY_COLUMNS: u32 = 4;
+/// Yards along a street are this far apart, so their roads line up
+pub const YARD_WIDTH: f32 = GROUND_COLUMNS as f32 * TILE_SIZE;
+
+/// Width of the gate in front of the house door
+const WALK_GATE_WIDTH: f32 = 96.0;
+/// Space kept free around trees, so there is always room to walk between them
+const TREE_SPACING: f32 = 64.0;
+/// Generating starts over with the next numbers of the seed when a yard doesn't validate
+const MAX_ATTEMPTS: usize = 32;
+/// Random spots tried for a single tree or car before leaving it out
+const PLACEMENT_TRIES: usize = 16;
+
+pub struct YardPlugin;
+
+/// Measures the scenery once the textures are loaded and generates a yard from the `YardSeed`
+/// Without a seed the yard authored in `assets/levels/yard.level.ron` is used
+impl Plugin for YardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<YardSeed>()
+            // Loading is also left when a file failed, the textures are missing then
+            .add_systems(
+                OnExit(GameState::Loading),
+                generate_yard.run_if(resource_exists::<TextureAssets>),
+            );
+    }
+}
+
+/// The seed of the generated yard, the same seed always generates the same yard
+#[derive(Resource, Default, Clone, Copy)]
+pub struct YardSeed(pub Option<u64>);
+
+/// Sizes of the scenery in world units, generated yards are laid out around them
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Footprints {
+    pub house: Vec2,
+    pub tree: Vec2,
+    /// The largest of the car sprites
+    pub car: Vec2,
+    /// One tile of the horizontal fence
+    pub fence_horizontal: Vec2,
+    /// One tile of the vertical fence
+    pub fence_vertical: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CarColor {
+    Gray,
+    Blue,
+    Red,
+}
+
+impl CarColor {
+    const ALL: [CarColor; 3] = [CarColor::Gray, CarColor::Blue, CarColor::Red];
+
+    pub fn texture(self, textures: &TextureAssets) -> Handle<Image> {
+        match self {
+            CarColor::Gray => textures.car_right_gray.clone(),
+            CarColor::Blue => textures.car_right_blue.clone(),
+            CarColor::Red => textures.car_right_red.clone(),
+        }
+    }
+}
+
+/// A straight run of fence, gates are the gaps between runs
+#[derive(Clone, Copy, Debug)]
+pub struct Fence {
+    pub bounds: Aabb2d,
+    pub horizontal: bool,
+    /// How many fence tiles it is long
+    pub tiles: f32,
+}
+
+/// Something of a generated yard that is spawned on its own, see `scenery::spawn_yard_piece`
+#[derive(Clone, Copy, Debug)]
+pub enum YardPiece {
+    House,
+    Tree(usize),
+    Car(usize),
+    Fence(usize),
+    /// The invisible wall on the far side of the road
+    RoadBarrier,
+    /// The trigger covering the road
+    Road,
+}
+
+impl YardPiece {
+    /// The id the piece keeps its state in save games with, fences are never moved
+    pub fn prop_id(self) -> Option<String> {
+        match self {
+            YardPiece::House => Some("house".to_owned()),
+            YardPiece::Tree(index) => Some(format!("tree_{index}")),
+            YardPiece::Car(index) => Some(format!("car_{index}")),
+            YardPiece::Fence(_) | YardPiece::RoadBarrier | YardPiece::Road => None,
+        }
+    }
+}
+
+/// A generated yard in world units, spawned like the authored one by `ground` and `scenery`
+#[derive(Resource, Clone, Debug)]
+pub struct YardLayout {
+    /// The area inside the fences
+    pub bounds: Aabb2d,
+    /// Where the player starts, on the pavement in front of the gate to the house
+    pub start: Vec2,
+    pub house: Aabb2d,
+    /// First ground column of the driveway, it runs from the road up beside the house
+    driveway_column: u32,
+    driveway_rows: u32,
+    pub fences: Vec<Fence>,
+    pub trees: Vec<Aabb2d>,
+    pub cars: Vec<(CarColor, Aabb2d)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum YardError {
+    /// Two things in the yard overlap, named like their `Prop`s
+    Overlap(String, String),
+    /// Something that belongs inside the fences is outside of them
+    OutsideFences(String),
+    /// A spot the player has to get to can't be walked to from the start
+    Unreachable(&'static str),
+}
+
+impl fmt::Display for YardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YardError::Overlap(first, second) => write!(f, "{first} overlaps {second}"),
+            YardError::OutsideFences(name) => write!(f, "{name} is outside the fences"),
+            YardError::Unreachable(spot) => write!(f, "the {spot} can't be reached"),
+        }
+    }
+}
+
+impl std::error::Error for YardError {}
+
+impl YardLayout {
+    /// Lays out a yard from `seed`, trying again with more numbers of the seed until one validates
+    pub fn generate(seed: u64, footprints: &Footprints) -> Result<Self, YardError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut attempts = 1;
+        loop {
+            let yard = Self::attempt(footprints, &mut rng);
+            match yard.validate() {
+                Ok(()) => return Ok(yard),
+                Err(error) if attempts == MAX_ATTEMPTS => return Err(error),
+                Err(_) => attempts += 1,
+            }
+        }
+    }
+
+    fn attempt(footprints: &Footprints, rng: &mut StdRng) -> Self {
+        let road_top = GROUND_ORIGIN.y + (ROAD_ROWS as f32) * TILE_SIZE;
+        let side = rng.gen_range(800.0..=960.0);
+        let back = rng.gen_range(600.0..=740.0);
+        let front = rng.gen_range(-520.0..=-400.0);
+        let bounds = Aabb2d {
+            min: Vec2::new(-side, front),
+            max: Vec2::new(side, back),
+        };
+
+        // The house faces the road, with its door in the middle of the front
+        let house_half = footprints.house / 2.0;
+        let house_x = rng.gen_range(-side + house_half.x + 96.0..=side - house_half.x - 96.0);
+        let house_y = front + rng.gen_range(160.0..=320.0) + house_half.y;
+        let house = Aabb2d::new(Vec2::new(house_x, house_y), house_half);
+
+        // The driveway leads from the road to the side of the house
+        let driveway_column = if rng.gen_bool(0.5) {
+            ((house.max.x + 24.0 - GROUND_ORIGIN.x) / TILE_SIZE).ceil() as u32
+        } else {
+            ((house.min.x - 24.0 - GROUND_ORIGIN.x) / TILE_SIZE).floor() as u32 - DRIVEWAY_COLUMNS
+        };
+        let driveway_rows = ((house_y - GROUND_ORIGIN.y) / TILE_SIZE).ceil() as u32;
+        let mut yard = YardLayout {
+            bounds,
+            start: Vec2::new(house_x, (road_top + front) / 2.0),
+            house,
+            driveway_column,
+            driveway_rows,
+            fences: Vec::new(),
+            trees: Vec::new(),
+            cars: Vec::new(),
+        };
+        let driveway = yard.driveway();
+
+        // Fences all around, the front one has gates for the driveway and the walk to the door
+        let mut gates = vec![
+            (driveway.min.x - 16.0, driveway.max.x + 16.0),
+            (
+                house_x - WALK_GATE_WIDTH / 2.0,
+                house_x + WALK_GATE_WIDTH / 2.0,
+            ),
+        ];
+        gates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut from = -side;
+        for (gate_start, gate_end) in gates {
+            yard.add_horizontal_fence(from, gate_start, front, footprints);
+            from = from.max(gate_end);
+        }
+        yard.add_horizontal_fence(from, side, front, footprints);
+        yard.add_horizontal_fence(-side, side, back, footprints);
+        yard.add_vertical_fence(-side, front, back, footprints);
+        yard.add_vertical_fence(side, front, back, footprints);
+
+        // Cars are parked along the driveway
+        let car_half = footprints.car / 2.0;
+        for _ in 0..rng.gen_range(0..=3) {
+            let color = CarColor::ALL[rng.gen_range(0..CarColor::ALL.len())];
+            let spot = (0..PLACEMENT_TRIES).find_map(|_| {
+                let y = rng.gen_range(road_top + car_half.y..=driveway.max.y - car_half.y);
+                let car = Aabb2d::new(Vec2::new(driveway.center().x, y), car_half);
+                (!yard.is_occupied(&car, 8.0)).then_some(car)
+            });
+            if let Some(car) = spot {
+                yard.cars.push((color, car));
+            }
+        }
+
+        // Trees anywhere inside the fences that is left, with room to walk around them
+        let tree_half = footprints.tree / 2.0;
+        let inside = bounds.shrink(tree_half + Vec2::splat(footprints.fence_vertical.x));
+        for _ in 0..rng.gen_range(4..=12) {
+            let spot = (0..PLACEMENT_TRIES).find_map(|_| {
+                let x = rng.gen_range(inside.min.x..=inside.max.x);
+                let y = rng.gen_range(inside.min.y..=inside.max.y);
+                let tree = Aabb2d::new(Vec2::new(x, y), tree_half);
+                let blocked = yard.is_occupied(&tree, TREE_SPACING)
+                    || tree.grow(Vec2::splat(TREE_SPACING)).intersects(&driveway);
+                (!blocked).then_some(tree)
+            });
+            if let Some(tree) = spot {
+                yard.trees.push(tree);
+            }
+        }
+        yard
+    }
+
+    fn add_horizontal_fence(&mut self, from: f32, to: f32, y: f32, footprints: &Footprints) {
+        if to - from < footprints.fence_horizontal.x {
+            return;
+        }
+        self.fences.push(Fence {
+            bounds: Aabb2d {
+                min: Vec2::new(from, y - footprints.fence_horizontal.y / 2.0),
+                max: Vec2::new(to, y + footprints.fence_horizontal.y / 2.0),
+            },
+            horizontal: true,
+            tiles: (to - from) / footprints.fence_horizontal.x,
+        });
+    }
+
+    fn add_vertical_fence(&mut self, x: f32, from: f32, to: f32, footprints: &Footprints) {
+        self.fences.push(Fence {
+            bounds: Aabb2d {
+                min: Vec2::new(x - footprints.fence_vertical.x / 2.0, from),
+                max: Vec2::new(x + footprints.fence_vertical.x / 2.0, to),
+            },
+            horizontal: false,
+            tiles: (to - from) / footprints.fence_vertical.y,
+        });
+    }
+
+    /// Whether `bounds` grown by `margin` runs into anything placed so far
+    fn is_occupied(&self, bounds: &Aabb2d, margin: f32) -> bool {
+        let grown = bounds.grow(Vec2::splat(margin));
+        self.obstacles()
+            .any(|(_, obstacle)| obstacle.intersects(&grown))
+    }
+
+    /// The driveway in world units, from the bottom of the road up beside the house
+    pub fn driveway(&self) -> Aabb2d {
+        let road_top = GROUND_ORIGIN.y + (ROAD_ROWS as f32) * TILE_SIZE;
+        let min = GROUND_ORIGIN + Vec2::new(self.driveway_column as f32 * TILE_SIZE, 0.0);
+        let max = GROUND_ORIGIN
+            + Vec2::new(
+                (self.driveway_column + DRIVEWAY_COLUMNS) as f32 * TILE_SIZE,
+                self.driveway_rows as f32 * TILE_SIZE,
+            );
+        Aabb2d {
+            min: Vec2::new(min.x, road_top),
+            max,
+        }
+    }
+
+    /// Everything in the yard, in the order it is spawned
+    pub fn pieces(&self) -> impl Iterator<Item = YardPiece> {
+        std::iter::once(YardPiece::House)
+            .chain((0..self.trees.len()).map(YardPiece::Tree))
+            .chain((0..self.cars.len()).map(YardPiece::Car))
+            .chain((0..self.fences.len()).map(YardPiece::Fence))
+            .chain([YardPiece::RoadBarrier, YardPiece::Road])
+    }
+
+    /// The `Prop` ids of the house, trees and cars with their bounds
+    fn props(&self) -> impl Iterator<Item = (String, Aabb2d)> + '_ {
+        std::iter::once(("house".to_owned(), self.house))
+            .chain(
+                self.trees
+                    .iter()
+                    .enumerate()
+                    .map(|(index, tree)| (format!("tree_{index}"), *tree)),
+            )
+            .chain(
+                self.cars
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (_, car))| (format!("car_{index}"), *car)),
+            )
+    }
+
+    /// Everything that blocks walking, the props and the fences
+    fn obstacles(&self) -> impl Iterator<Item = (String, Aabb2d)> + '_ {
+        self.props().chain(
+            self.fences
+                .iter()
+                .enumerate()
+                .map(|(index, fence)| (format!("fence_{index}"), fence.bounds)),
+        )
+    }
+
+    /// The driveway and road as a ground layer, like the one of an authored level
+    pub fn ground(&self) -> TileLayer {
+        let driveway = self.driveway_column..self.driveway_column + DRIVEWAY_COLUMNS;
+        let rows = (0..self.driveway_rows.max(ROAD_ROWS))
+            .rev()
+            .map(|row| {
+                (0..GROUND_COLUMNS)
+                    .map(|column| {
+                        let paved = row < ROAD_ROWS
+                            || (driveway.contains(&column) && row < self.driveway_rows);
+                        if paved {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        TileLayer {
+            origin: GROUND_ORIGIN,
+            tile_size: TILE_SIZE,
+            rows,
+        }
+    }
+
+    /// Checks that nothing overlaps and that the door and the back of the house can be walked to
+    pub fn validate(&self) -> Result<(), YardError> {
+        let props: Vec<(String, Aabb2d)> = self.props().collect();
+        let obstacles: Vec<(String, Aabb2d)> = self.obstacles().collect();
+
+        // Props can't overlap each other or the fences, fences only touch at the corners
+        for (index, (name, bounds)) in props.iter().enumerate() {
+            if let Some((other, _)) = obstacles
+                .iter()
+                .skip(index + 1)
+                .find(|(_, other)| other.intersects(bounds))
+            {
+                return Err(YardError::Overlap(name.clone(), other.clone()));
+            }
+        }
+        let driveway = self.driveway();
+        for (name, bounds) in props.iter().take(self.trees.len() + 1) {
+            if bounds.intersects(&driveway) {
+                return Err(YardError::Overlap(name.clone(), "driveway".to_owned()));
+            }
+            if !self.bounds.contains(bounds) {
+                return Err(YardError::OutsideFences(name.clone()));
+            }
+        }
+        if driveway.min.x < self.bounds.min.x || driveway.max.x > self.bounds.max.x {
+            return Err(YardError::OutsideFences("driveway".to_owned()));
+        }
+        let start = character_bounds(self.start.extend(0.0));
+        if let Some((name, _)) = obstacles.iter().find(|(_, other)| other.intersects(&start)) {
+            return Err(YardError::Overlap("player start".to_owned(), name.clone()));
+        }
+
+        // Walk from the start to the door and around the house
+        let mut grid = NavGrid::new(WORLD_MIN, WORLD_MAX, CELL_SIZE);
+        for (index, (_, bounds)) in obstacles.iter().enumerate() {
+            grid.insert(Entity::from_raw(index as u32), &[*bounds]);
+        }
+        let door = Vec2::new(self.house.center().x, self.house.min.y - 24.0);
+        let back = Vec2::new(
+            self.house.center().x,
+            (self.house.max.y + self.bounds.max.y) / 2.0,
+        );
+        for (spot, position) in [("house door", door), ("back of the house", back)] {
+            if !grid.is_walkable_at(position) || grid.find_path(self.start, position).is_none() {
+                return Err(YardError::Unreachable(spot));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn generate_yard(
+    mut commands: Commands,
+    seed: Res<YardSeed>,
+    textures: Res<TextureAssets>,
+    images: Res<Assets<Image>>,
+) {
+    let size = |texture: &Handle<Image>| {
+        images
+            .get(texture)
+            .map(|image| image.size().as_vec2() * SCENERY_SCALE)
+    };
+    let car = [
+        &textures.car_right_gray,
+        &textures.car_right_blue,
+        &textures.car_right_red,
+    ]
+    .into_iter()
+    .filter_map(size)
+    .reduce(Vec2::max);
+    let (Some(house), Some(tree), Some(car), Some(fence_horizontal), Some(fence_vertical)) = (
+        size(&textures.house),
+        size(&textures.tree1),
+        car,
+        size(&textures.fence_horizontal),
+        size(&textures.fence_vertical),
+    ) else {
+        error!("The scenery textures aren't loaded, no yards can be generated");
+        return;
+    };
+    let footprints = Footprints {
+        house,
+        tree,
+        car,
+        fence_horizontal,
+        fence_vertical,
+    };
+    commands.insert_resource(footprints);
+    let Some(seed) = seed.0 else {
+        return;
+    };
+    match YardLayout::generate(seed, &footprints) {
+        Ok(yard) => {
+            info!(
+                "Generated yard {seed} with {} trees and {} cars",
+                yard.trees.len(),
+                yard.cars.len()
+            );
+            commands.insert_resource(yard);
+        }
+        Err(error) => error!("Failed to generate yard {seed}, keeping the authored one: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// About the size of the scenery textures in world units
+    const FOOTPRINTS: Footprints = Footprints {
+        house: Vec2::new(320.0, 260.0),
+        tree: Vec2::new(96.0, 112.0),
+        car: Vec2::new(150.0, 72.0),
+        fence_horizontal: Vec2::new(64.0, 24.0),
+        fence_vertical: Vec2::new(16.0, 64.0),
+    };
+
+    /// `generate` only returns yards that validate, so this is about it giving up too early
+    #[test]
+    fn every_seed_generates_a_yard() {
+        for seed in 0..64 {
+            if let Err(error) = YardLayout::generate(seed, &FOOTPRINTS) {
+                panic!("yard {seed}: {error}");
+            }
+        }
+    }
+
+    /// A house with its door facing the road, a tree behind it and a car on the driveway
+    fn hand_built() -> YardLayout {
+        let mut yard = YardLayout {
+            bounds: Aabb2d {
+                min: Vec2::new(-900.0, -480.0),
+                max: Vec2::new(900.0, 700.0),
+            },
+            start: Vec2::new(0.0, -600.0),
+            house: Aabb2d::new(Vec2::new(0.0, -200.0), FOOTPRINTS.house / 2.0),
+            // From x = 192 to 384, and from the road up to y = -192
+            driveway_column: 44,
+            driveway_rows: 15,
+            fences: Vec::new(),
+            trees: vec![Aabb2d::new(Vec2::new(-500.0, 300.0), FOOTPRINTS.tree / 2.0)],
+            cars: vec![(
+                CarColor::Red,
+                Aabb2d::new(Vec2::new(288.0, -600.0), FOOTPRINTS.car / 2.0),
+            )],
+        };
+        // Gates for the walk to the door and for the driveway
+        yard.add_horizontal_fence(-900.0, -48.0, -480.0, &FOOTPRINTS);
+        yard.add_horizontal_fence(48.0, 176.0, -480.0, &FOOTPRINTS);
+        yard.add_horizontal_fence(400.0, 900.0, -480.0, &FOOTPRINTS);
+        yard.add_horizontal_fence(-900.0, 900.0, 700.0, &FOOTPRINTS);
+        yard.add_vertical_fence(-900.0, -480.0, 700.0, &FOOTPRINTS);
+        yard.add_vertical_fence(900.0, -480.0, 700.0, &FOOTPRINTS);
+        yard
+    }
+
+    #[test]
+    fn hand_built_yard_validates() {
+        assert_eq!(hand_built().validate(), Ok(()));
+    }
+
+    #[test]
+    fn catches_a_tree_overlapping_the_house() {
+        let mut yard = hand_built();
+        yard.trees[0] = Aabb2d::new(Vec2::new(100.0, -100.0), FOOTPRINTS.tree / 2.0);
+        assert_eq!(
+            yard.validate(),
+            Err(YardError::Overlap("house".to_owned(), "tree_0".to_owned()))
+        );
+    }
+
+    #[test]
+    fn catches_a_tree_outside_the_fences() {
+        let mut yard = hand_built();
+        yard.trees[0] = Aabb2d::new(Vec2::new(-1000.0, 300.0), FOOTPRINTS.tree / 2.0);
+        assert_eq!(
+            yard.validate(),
+            Err(YardError::OutsideFences("tree_0".to_owned()))
+        );
+    }
+
+    #[test]
+    fn catches_a_walled_off_door() {
+        let mut yard = hand_built();
+        yard.add_horizontal_fence(-48.0, 48.0, -480.0, &FOOTPRINTS);
+        yard.add_horizontal_fence(176.0, 400.0, -480.0, &FOOTPRINTS);
+        assert_eq!(yard.validate(), Err(YardError::Unreachable("house door")));
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_yard() {
+        for seed in [0, 1, 42, u64::MAX] {
+            let first = YardLayout::generate(seed, &FOOTPRINTS).map(|yard| format!("{yard:?}"));
+            let second = YardLayout::generate(seed, &FOOTPRINTS).map(|yard| format!("{yard:?}"));
+            assert_eq!(first, second, "yard {seed}");
+        }
+    }
+}